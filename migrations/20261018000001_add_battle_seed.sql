@@ -0,0 +1,2 @@
+-- Seed used to simulate the card battle of each match, allows replaying disputed matches
+ALTER TABLE match_sets ADD COLUMN battle_seed BIGINT;
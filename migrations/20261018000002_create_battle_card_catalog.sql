@@ -0,0 +1,32 @@
+-- Stats of every battle card, previously hard-coded in Strike::new() and Block::new()
+CREATE TABLE battle_card_catalog (
+    name TEXT NOT NULL,
+    skill TEXT NOT NULL CHECK (skill IN ('strike', 'block')),
+    damage REAL NOT NULL DEFAULT 0,
+    accuracy REAL NOT NULL DEFAULT 0,
+    damage_reduction REAL NOT NULL DEFAULT 0,
+    -- Name of the strike a block cancels
+    strike_to_cancel TEXT CHECK (skill <> 'block' OR strike_to_cancel IS NOT NULL),
+    effect JSONB NOT NULL,
+    version INT NOT NULL DEFAULT 1,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (name, skill)
+);
+
+INSERT INTO battle_card_catalog (name, skill, damage, accuracy, effect) VALUES
+    ('leg_strike', 'strike', 5.0, 0.9, '{"action": "Increase", "amount": 0.5, "stat": "Accuracy", "target": "Owner"}'),
+    ('temple_strike', 'strike', 10.0, 0.75, '{"action": "Decrease", "amount": 0.5, "stat": "Accuracy", "target": "Opponent"}'),
+    ('shoulder_strike', 'strike', 10.0, 0.8, '{"action": "Decrease", "amount": 0.1, "stat": "Accuracy", "target": "Opponent"}'),
+    ('shoulder_thrust', 'strike', 8.0, 0.85, '{"action": "Decrease", "amount": 0.1, "stat": "Accuracy", "target": "Opponent"}'),
+    ('eye_poke', 'strike', 12.0, 0.6, '{"action": "Decrease", "amount": 0.15, "stat": "Accuracy", "target": "Opponent"}'),
+    ('stomach_thrust', 'strike', 10.0, 0.85, '{"action": "Increase", "amount": 0.5, "stat": "Damage", "target": "Owner"}'),
+    ('head_strike', 'strike', 18.0, 0.5, '{"action": "Decrease", "amount": 0.15, "stat": "Accuracy", "target": "Opponent"}');
+
+INSERT INTO battle_card_catalog (name, skill, damage_reduction, strike_to_cancel, effect) VALUES
+    ('leg_strike', 'block', 0.1, 'leg_strike', '{"action": "Increase", "amount": 0.1, "stat": "Accuracy", "target": "Owner"}'),
+    ('temple_strike', 'block', 0.15, 'temple_strike', '{"action": "Decrease", "amount": 0.1, "stat": "Accuracy", "target": "Opponent"}'),
+    ('shoulder_strike', 'block', 0.15, 'shoulder_strike', '{"action": "Increase", "amount": 0.5, "stat": "Damage", "target": "Owner"}'),
+    ('shoulder_thrust', 'block', 0.15, 'shoulder_thrust', '{"action": "Decrease", "amount": 0.1, "stat": "Accuracy", "target": "Opponent"}'),
+    ('eye_poke', 'block', 0.15, 'eye_poke', '{"action": "Decrease", "amount": 0.1, "stat": "Damage", "target": "Opponent"}'),
+    ('stomach_thrust', 'block', 0.15, 'stomach_thrust', '{"action": "Increase", "amount": 0.5, "stat": "Damage", "target": "Owner"}'),
+    ('head_strike', 'block', 0.15, 'head_strike', '{"action": "Decrease", "amount": 0.2, "stat": "Damage", "target": "Opponent"}');
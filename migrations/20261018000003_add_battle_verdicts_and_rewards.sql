@@ -0,0 +1,15 @@
+-- Outcome of the card battle for each user, scored with the battle_rewards table
+ALTER TABLE match_sets
+    ADD COLUMN user1_battle_verdict TEXT CHECK (user1_battle_verdict IN ('win', 'lose', 'draw', 'forfeit')),
+    ADD COLUMN user2_battle_verdict TEXT CHECK (user2_battle_verdict IN ('win', 'lose', 'draw', 'forfeit'));
+
+CREATE TABLE battle_rewards (
+    verdict TEXT PRIMARY KEY CHECK (verdict IN ('win', 'lose', 'draw', 'forfeit')),
+    score INT NOT NULL
+);
+
+INSERT INTO battle_rewards (verdict, score) VALUES
+    ('win', 10),
+    ('draw', 5),
+    ('lose', 0),
+    ('forfeit', 0);
@@ -0,0 +1,17 @@
+-- One card battle run per section and set, a completed set is only simulated again on an explicit re-run
+CREATE TABLE battle_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    section TEXT NOT NULL,
+    set INT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'running', 'completed', 'failed')),
+    error TEXT,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (section, set)
+);
+
+-- Scores awarded by the card battle, reversed before a re-run
+ALTER TABLE match_sets
+    ADD COLUMN user1_battle_score INT,
+    ADD COLUMN user2_battle_score INT;
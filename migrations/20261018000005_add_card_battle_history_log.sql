@@ -0,0 +1,2 @@
+-- Full per-turn state of the battle: multipliers before and after the turn, roll, accuracy and consumed effect
+ALTER TABLE card_battle_history ADD COLUMN log JSONB;
@@ -0,0 +1,24 @@
+-- New card families: dodges avoid any strike, feints bait blocks and counters reflect strikes
+ALTER TABLE battle_card_catalog DROP CONSTRAINT battle_card_catalog_skill_check;
+ALTER TABLE battle_card_catalog ADD CONSTRAINT battle_card_catalog_skill_check
+    CHECK (skill IN ('strike', 'block', 'dodge', 'feint', 'counter'));
+
+-- Share of the incoming strike damage a counter sends back
+ALTER TABLE battle_card_catalog ADD COLUMN reflect REAL NOT NULL DEFAULT 0;
+
+INSERT INTO battle_card_catalog (name, skill, effect) VALUES
+    ('sidestep', 'dodge', '{"action": "Decrease", "amount": 0.2, "stat": "Accuracy", "target": "Owner"}'),
+    ('backstep', 'dodge', '{"action": "Decrease", "amount": 0.1, "stat": "Accuracy", "target": "Owner", "duration": 2}');
+
+INSERT INTO battle_card_catalog (name, skill, damage, accuracy, effect) VALUES
+    ('high_feint', 'feint', 8.0, 0.9, '{"action": "Decrease", "amount": 0.1, "stat": "Damage", "target": "Opponent"}'),
+    ('low_feint', 'feint', 6.0, 0.95, '{"action": "Increase", "amount": 0.2, "stat": "Accuracy", "target": "Owner"}');
+
+INSERT INTO battle_card_catalog (name, skill, reflect, effect) VALUES
+    ('parry', 'counter', 0.5, '{"action": "Decrease", "amount": 0.1, "stat": "Accuracy", "target": "Opponent"}'),
+    ('disarm', 'counter', 0.3, '{"action": "Decrease", "amount": 0.2, "stat": "Damage", "target": "Opponent", "duration": 2}');
+
+-- Skill of the played card, and the interaction between both cards of the turn
+ALTER TABLE card_battle_history
+    ADD COLUMN card_skill TEXT,
+    ADD COLUMN interaction TEXT CHECK (interaction IN ('cancel', 'dodge', 'feint', 'counter'));
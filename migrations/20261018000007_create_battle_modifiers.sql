@@ -0,0 +1,18 @@
+-- The week's arnis skill and footwork from match_sets change the card battle
+-- Both tables start empty: the keys are the free text skill and footwork the admin sends on
+-- matchmaking, so admins fill them in through /card_battle/modifiers with the values they use.
+-- Until then no strike or block is changed.
+
+-- Strikes practiced with a skill gain accuracy
+CREATE TABLE battle_skill_modifiers (
+    arnis_skill TEXT NOT NULL,
+    card_name TEXT NOT NULL,
+    accuracy REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (arnis_skill, card_name)
+);
+
+-- Added to the damage reduction of every block, can be negative
+CREATE TABLE battle_footwork_modifiers (
+    arnis_footwork TEXT PRIMARY KEY,
+    damage_reduction REAL NOT NULL DEFAULT 0
+);
@@ -0,0 +1,20 @@
+-- What active power cards do in the card battle
+CREATE TABLE battle_power_card_modifiers (
+    name TEXT PRIMARY KEY,
+    -- Multiply the damage the owner deals and takes
+    damage_dealt REAL NOT NULL DEFAULT 1,
+    damage_taken REAL NOT NULL DEFAULT 1,
+    -- Least damage of the owner's hits, as a share of the card's base damage
+    damage_floor REAL NOT NULL DEFAULT 0
+);
+
+INSERT INTO battle_power_card_modifiers (name, damage_dealt, damage_taken, damage_floor) VALUES
+    ('Double-edged Sword', 2, 2, 0),
+    ('Ancient''s Protection', 1, 1, 0.5);
+
+-- Power cards active when the match was first simulated, kept for replays
+ALTER TABLE match_sets
+    ADD COLUMN user1_battle_power_cards TEXT[],
+    ADD COLUMN user2_battle_power_cards TEXT[];
+
+ALTER TABLE card_battle_history ADD COLUMN power_modifier TEXT;
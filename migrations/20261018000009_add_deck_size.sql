@@ -0,0 +1,3 @@
+-- Number of cards in a battle deck, set per section and copied to every matchmade set
+ALTER TABLE sections ADD COLUMN deck_size SMALLINT NOT NULL DEFAULT 6 CHECK (deck_size > 0);
+ALTER TABLE match_sets ADD COLUMN deck_size SMALLINT NOT NULL DEFAULT 6 CHECK (deck_size > 0);
@@ -0,0 +1,17 @@
+-- First deck submission of every user per match, late if it came after card_deadline
+CREATE TABLE battle_deck_submissions (
+    match_set_id UUID NOT NULL REFERENCES match_sets(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    is_late BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (match_set_id, user_id)
+);
+
+-- Deadlines extended by the admin, late decks are rejected without one
+CREATE TABLE battle_deck_extensions (
+    match_set_id UUID NOT NULL REFERENCES match_sets(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    deadline TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (match_set_id, user_id)
+);
@@ -0,0 +1,2 @@
+-- Last edit of a submitted deck, NULL if it was never changed
+ALTER TABLE battle_deck_submissions ADD COLUMN updated_at TIMESTAMPTZ;
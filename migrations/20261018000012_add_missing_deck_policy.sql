@@ -0,0 +1,13 @@
+-- What the card battle does for the users who did not submit a deck
+ALTER TABLE sections
+    ADD COLUMN missing_deck_policy TEXT NOT NULL DEFAULT 'forfeit'
+        CHECK (missing_deck_policy IN ('forfeit', 'random', 'default', 'previous')),
+    -- Cards as {"name", "skill"} objects, used by the "default" policy
+    ADD COLUMN default_deck JSONB NOT NULL DEFAULT '[]';
+
+-- Policy applied to each user of the match, NULL if the user submitted a deck
+ALTER TABLE match_sets
+    ADD COLUMN user1_deck_policy TEXT
+        CHECK (user1_deck_policy IN ('forfeit', 'random', 'default', 'previous')),
+    ADD COLUMN user2_deck_policy TEXT
+        CHECK (user2_deck_policy IN ('forfeit', 'random', 'default', 'previous'));
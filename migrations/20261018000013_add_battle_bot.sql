@@ -0,0 +1,12 @@
+-- Plays the leftover user of a section with an odd number of users
+INSERT INTO users (id, email, section, first_name, last_name, age, contact_number, sex, role)
+VALUES ('b0000000-0000-0000-0000-000000000000', 'bot@kalikalihim.local', '', 'Kali', 'Bot', 0, '', 0, 'bot')
+ON CONFLICT (id) DO NOTHING;
+
+-- How the deck of the bot is built on every match
+ALTER TABLE sections
+    ADD COLUMN bot_deck_strategy TEXT NOT NULL DEFAULT 'random'
+        CHECK (bot_deck_strategy IN ('random', 'default', 'mirror'));
+
+-- Score of a verdict on a match against the bot, the regular score is used if NULL
+ALTER TABLE battle_rewards ADD COLUMN bot_score INTEGER;
@@ -0,0 +1,12 @@
+-- HP knockout mode, every player starts with hp_pool and the match ends once one runs out
+-- NULL keeps the summed damage mode
+ALTER TABLE sections ADD COLUMN hp_pool REAL CHECK (hp_pool > 0);
+
+ALTER TABLE match_sets
+    ADD COLUMN hp_pool REAL CHECK (hp_pool > 0),
+    ADD COLUMN knockout_turn SMALLINT,
+    ADD COLUMN user1_remaining_hp REAL,
+    ADD COLUMN user2_remaining_hp REAL;
+
+-- HP of the player at the end of the turn
+ALTER TABLE card_battle_history ADD COLUMN remaining_hp REAL;
@@ -0,0 +1,8 @@
+-- Critical hits and damage spread of strikes, the defaults keep the damage fixed
+ALTER TABLE battle_card_catalog
+    ADD COLUMN crit_chance REAL NOT NULL DEFAULT 0 CHECK (crit_chance >= 0 AND crit_chance <= 1),
+    ADD COLUMN crit_multiplier REAL NOT NULL DEFAULT 1.5 CHECK (crit_multiplier >= 1),
+    -- Hits deal up to this share more or less damage
+    ADD COLUMN damage_spread REAL NOT NULL DEFAULT 0 CHECK (damage_spread >= 0 AND damage_spread < 1);
+
+ALTER TABLE card_battle_history ADD COLUMN is_critical BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,11 @@
+-- Cards played on consecutive turns that unlock a bonus on the last one
+CREATE TABLE battle_combos (
+    name TEXT PRIMARY KEY,
+    -- Catalog cards as [{"name", "skill"}], only strikes and blocks
+    sequence JSONB NOT NULL,
+    -- Added to the last card if it hits, a combo finished by a block only gives its effect
+    bonus_damage REAL NOT NULL DEFAULT 0 CHECK (bonus_damage >= 0),
+    effect JSONB
+);
+
+ALTER TABLE card_battle_history ADD COLUMN combo TEXT;
@@ -0,0 +1,4 @@
+-- Completed runs are revealed turn by turn, one turn every interval from the start of the reveal
+ALTER TABLE battle_runs
+    ADD COLUMN turn_interval_ms INT NOT NULL DEFAULT 2000 CHECK (turn_interval_ms >= 0),
+    ADD COLUMN reveal_started_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+-- Cards and rules a match was last simulated with, replays use them instead of the live
+-- catalog and modifiers so later balance edits don't change past results
+ALTER TABLE match_sets ADD COLUMN battle_snapshot JSONB;
@@ -0,0 +1,5 @@
+-- Hits are recorded on their own since a hit can land for no damage
+ALTER TABLE card_battle_history ADD COLUMN is_hit BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Best guess for the turns simulated before
+UPDATE card_battle_history SET is_hit = damage > 0;
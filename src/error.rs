@@ -2,12 +2,14 @@ use axum::{
     http::{self, StatusCode},
     response::{IntoResponse, Response},
 };
+use serde_json::json;
 use tracing::error;
 
 #[derive(Debug)]
 pub struct AppError {
     message: String,
     code: http::StatusCode,
+    details: Option<serde_json::Value>,
 }
 
 impl AppError {
@@ -15,8 +17,26 @@ impl AppError {
         Self {
             code,
             message: message.into(),
+            details: None,
         }
     }
+
+    // Responds with JSON instead of plain text so the frontend can show what went wrong
+    pub fn with_details(
+        code: http::StatusCode,
+        message: impl Into<String>,
+        details: serde_json::Value,
+    ) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: Some(details),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl From<serde_json::error::Error> for AppError {
@@ -24,6 +44,7 @@ impl From<serde_json::error::Error> for AppError {
         AppError {
             code: http::StatusCode::INTERNAL_SERVER_ERROR,
             message: format!("Serde JSON Error:\n{}", error),
+            details: None,
         }
     }
 }
@@ -55,6 +76,7 @@ impl From<sqlx::Error> for AppError {
         AppError {
             code,
             message: format!("SQLx Error:\n{}", error),
+            details: None,
         }
     }
 }
@@ -64,6 +86,7 @@ impl From<anyhow::Error> for AppError {
         AppError {
             code: http::StatusCode::INTERNAL_SERVER_ERROR,
             message: format!("Anyhow Error:\n{}", error),
+            details: None,
         }
     }
 }
@@ -72,6 +95,13 @@ impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         error!("{} {:<12} - {}", "ERROR", self.code, self.message);
 
-        (self.code, self.message).into_response()
+        match self.details {
+            Some(details) => (
+                self.code,
+                axum::Json(json!({ "message": self.message, "details": details })),
+            )
+                .into_response(),
+            None => (self.code, self.message).into_response(),
+        }
     }
 }
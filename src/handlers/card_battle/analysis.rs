@@ -0,0 +1,202 @@
+use anyhow::Context;
+use axum::{extract, http, response::Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+use super::{
+    build_deck,
+    catalog::Catalog,
+    model::{Card, DeckCard, PlayerTurn},
+    rules::BattleRules,
+    simulate_match, validate_practice_decks,
+};
+
+const DEFAULT_ITERATIONS: u32 = 1000;
+const MAX_ITERATIONS: u32 = 100_000;
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeMatchup {
+    user1: Vec<DeckCard>,
+    user2: Vec<DeckCard>,
+    iterations: Option<u32>,
+    seed: Option<i64>,
+    arnis_skill: Option<String>,
+    arnis_footwork: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DamageStats {
+    mean: f64,
+    variance: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchupAnalysis {
+    iterations: u32,
+    seed: i64,
+    // Probabilities are from user1's point of view
+    win: f64,
+    draw: f64,
+    loss: f64,
+    user1_damage: DamageStats,
+    user2_damage: DamageStats,
+    // None if the card played on that turn is not a strike
+    user1_hit_rates: Vec<Option<f64>>,
+    user2_hit_rates: Vec<Option<f64>>,
+}
+
+// Simulates one deck against another many times to get numbers for balancing the cards
+pub async fn analyze_matchup(
+    extract::State(pool): extract::State<PgPool>,
+    axum::Json(payload): axum::Json<AnalyzeMatchup>,
+) -> Result<axum::Json<MatchupAnalysis>, AppError> {
+    let iterations = payload.iterations.unwrap_or(DEFAULT_ITERATIONS);
+
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return Err(AppError::new(
+            http::StatusCode::BAD_REQUEST,
+            format!("Iterations must be between 1 and {}.", MAX_ITERATIONS),
+        ));
+    }
+
+    let seed = payload.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+    let catalog = Catalog::fetch(&pool).await?;
+    // Deck length times the iterations is what a single request costs
+    validate_practice_decks(&catalog, (&payload.user1, &payload.user2))?;
+
+    let deck_size = payload.user1.len().max(payload.user2.len());
+    let user1_cards = build_deck(
+        &catalog,
+        deck_size,
+        payload.user1.iter().map(DeckCard::as_pair),
+    );
+    let user2_cards = build_deck(
+        &catalog,
+        deck_size,
+        payload.user2.iter().map(DeckCard::as_pair),
+    );
+    let rules = BattleRules::fetch(
+        &pool,
+        payload.arnis_skill.as_deref(),
+        payload.arnis_footwork.as_deref(),
+    )
+    .await?;
+
+    // Thousands of simulations would otherwise block the runtime
+    let analysis = tokio::task::spawn_blocking(move || {
+        run_matchup((&user1_cards, &user2_cards), iterations, seed, &rules)
+    })
+    .await
+    .context("Matchup analysis was interrupted.")??;
+
+    Ok(axum::Json(analysis))
+}
+
+fn run_matchup(
+    (user1_cards, user2_cards): (&Vec<Option<Card>>, &Vec<Option<Card>>),
+    iterations: u32,
+    seed: i64,
+    rules: &BattleRules,
+) -> anyhow::Result<MatchupAnalysis, AppError> {
+    // Every iteration gets its own seed so any single run can be reproduced with /simulate
+    let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+    // Same as /simulate, so its nil ids reproduce the iterations
+    let users = (&uuid::Uuid::nil(), &uuid::Uuid::nil());
+
+    let (mut wins, mut draws, mut losses) = (0u32, 0u32, 0u32);
+    let mut user1_totals: Vec<f64> = Vec::with_capacity(iterations as usize);
+    let mut user2_totals: Vec<f64> = Vec::with_capacity(iterations as usize);
+    let mut user1_hits = TurnHits::new(user1_cards.len());
+    let mut user2_hits = TurnHits::new(user2_cards.len());
+
+    for _ in 0..iterations {
+        let (user1_turns, user2_turns) =
+            simulate_match((user1_cards, user2_cards), rng.gen(), users, rules)?;
+
+        let user1_total: f32 = user1_turns.iter().map(|turn| turn.damage).sum();
+        let user2_total: f32 = user2_turns.iter().map(|turn| turn.damage).sum();
+
+        if user1_total > user2_total {
+            wins += 1;
+        } else if user1_total < user2_total {
+            losses += 1;
+        } else {
+            draws += 1;
+        }
+
+        user1_totals.push(user1_total as f64);
+        user2_totals.push(user2_total as f64);
+        user1_hits.record(user1_cards, &user1_turns);
+        user2_hits.record(user2_cards, &user2_turns);
+    }
+
+    let total = iterations as f64;
+
+    Ok(MatchupAnalysis {
+        iterations,
+        seed,
+        win: wins as f64 / total,
+        draw: draws as f64 / total,
+        loss: losses as f64 / total,
+        user1_damage: DamageStats::new(&user1_totals),
+        user2_damage: DamageStats::new(&user2_totals),
+        user1_hit_rates: user1_hits.rates(),
+        user2_hit_rates: user2_hits.rates(),
+    })
+}
+
+impl DamageStats {
+    fn new(totals: &[f64]) -> Self {
+        let count = totals.len() as f64;
+        let mean = totals.iter().sum::<f64>() / count;
+        let variance = totals
+            .iter()
+            .map(|total| (total - mean).powi(2))
+            .sum::<f64>()
+            / count;
+
+        DamageStats { mean, variance }
+    }
+}
+
+struct TurnHits {
+    strikes: Vec<u32>,
+    hits: Vec<u32>,
+}
+
+impl TurnHits {
+    fn new(deck_size: usize) -> Self {
+        TurnHits {
+            strikes: vec![0; deck_size],
+            hits: vec![0; deck_size],
+        }
+    }
+
+    fn record(&mut self, cards: &[Option<Card>], turns: &[PlayerTurn]) {
+        for (i, (card, turn)) in cards.iter().zip(turns.iter()).enumerate() {
+            if let Some(Card::Strike(_)) = card {
+                self.strikes[i] += 1;
+
+                if turn.is_hit {
+                    self.hits[i] += 1;
+                }
+            }
+        }
+    }
+
+    fn rates(&self) -> Vec<Option<f64>> {
+        self.strikes
+            .iter()
+            .zip(self.hits.iter())
+            .map(|(strikes, hits)| match strikes {
+                0 => None,
+                _ => Some(*hits as f64 / *strikes as f64),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgConnection};
+use tracing::info;
+
+use crate::error::AppError;
+
+use super::{catalog::Catalog, model::DeckCard, BattleMatch};
+
+// What the card battle does for a user who did not submit a deck, set per section
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingDeckPolicy {
+    Forfeit,
+    Random,   // Random cards from the catalog
+    Default,  // The default deck of the section
+    Previous, // The deck the user submitted on their previous match
+}
+
+impl MissingDeckPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MissingDeckPolicy::Forfeit => "forfeit",
+            MissingDeckPolicy::Random => "random",
+            MissingDeckPolicy::Default => "default",
+            MissingDeckPolicy::Previous => "previous",
+        }
+    }
+}
+
+// How the deck of the bot is built, set per section
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BotDeckStrategy {
+    Random,  // Random cards from the catalog
+    Default, // The default deck of the section
+    Mirror,  // The same deck as its opponent
+}
+
+impl BotDeckStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BotDeckStrategy::Random => "random",
+            BotDeckStrategy::Default => "default",
+            BotDeckStrategy::Mirror => "mirror",
+        }
+    }
+}
+
+type Decks = HashMap<(uuid::Uuid, uuid::Uuid), Vec<(String, String)>>;
+
+#[derive(Debug, FromRow)]
+struct SectionDecks {
+    missing_deck_policy: String,
+    default_deck: Json<Vec<DeckCard>>,
+    bot_deck_strategy: String,
+}
+
+// Filled decks are saved as the user's battle cards, so replays and re-runs use the same cards
+pub(super) async fn fill_missing_decks(
+    txn: &mut PgConnection,
+    catalog: &Catalog,
+    section: &str,
+    matches: &[BattleMatch],
+    decks: &mut Decks,
+) -> Result<(), AppError> {
+    let section_decks = sqlx::query_as::<_, SectionDecks>(
+        "SELECT missing_deck_policy, default_deck, bot_deck_strategy FROM sections WHERE id = ($1)",
+    )
+    .bind(section)
+    .fetch_optional(&mut *txn)
+    .await?
+    .unwrap_or(SectionDecks {
+        missing_deck_policy: "forfeit".to_string(),
+        default_deck: Json(Vec::new()),
+        bot_deck_strategy: "random".to_string(),
+    });
+
+    let policy: MissingDeckPolicy =
+        serde_json::from_value(serde_json::Value::String(section_decks.missing_deck_policy))?;
+    let bot_deck_strategy: BotDeckStrategy =
+        serde_json::from_value(serde_json::Value::String(section_decks.bot_deck_strategy))?;
+    let Json(default_deck) = section_decks.default_deck;
+
+    let bot_id = sqlx::query_scalar::<_, uuid::Uuid>("SELECT id FROM users WHERE role = 'bot'")
+        .fetch_optional(&mut *txn)
+        .await?;
+
+    for battle_match in matches {
+        let users = [battle_match.user1_id, battle_match.user2_id];
+
+        for user_id in users.into_iter().filter(|user_id| Some(*user_id) != bot_id) {
+            if decks.contains_key(&(battle_match.id, user_id)) {
+                continue;
+            }
+
+            let deck_size = battle_match.deck_size as usize;
+            let cards: Vec<(String, String)> = match policy {
+                MissingDeckPolicy::Forfeit => Vec::new(),
+                MissingDeckPolicy::Random => random_deck(catalog, deck_size),
+                MissingDeckPolicy::Default => section_deck(&default_deck, deck_size),
+                MissingDeckPolicy::Previous => {
+                    previous_deck(&mut *txn, &battle_match.id, &user_id, deck_size).await?
+                }
+            };
+
+            // Nothing to fill the deck with, e.g. no previous deck on the first match
+            let applied = if cards.is_empty() {
+                MissingDeckPolicy::Forfeit
+            } else {
+                policy
+            };
+
+            info!(
+                "Missing deck of {user_id} on {}: {}",
+                battle_match.id,
+                applied.as_str()
+            );
+
+            insert_filled_deck(&mut *txn, &battle_match.id, &user_id, &cards).await?;
+            record_policy(&mut *txn, &battle_match.id, &user_id, applied).await?;
+
+            if !cards.is_empty() {
+                decks.insert((battle_match.id, user_id), cards);
+            }
+        }
+
+        // The bot goes last so it can mirror a deck that was filled above
+        for (user_id, opponent_id) in [(users[0], users[1]), (users[1], users[0])] {
+            if Some(user_id) != bot_id || decks.contains_key(&(battle_match.id, user_id)) {
+                continue;
+            }
+
+            let deck_size = battle_match.deck_size as usize;
+            let cards = match bot_deck_strategy {
+                BotDeckStrategy::Random => Vec::new(),
+                BotDeckStrategy::Default => section_deck(&default_deck, deck_size),
+                BotDeckStrategy::Mirror => decks
+                    .get(&(battle_match.id, opponent_id))
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+
+            // The bot never forfeits
+            let cards = if cards.is_empty() {
+                random_deck(catalog, deck_size)
+            } else {
+                cards
+            };
+
+            insert_filled_deck(&mut *txn, &battle_match.id, &user_id, &cards).await?;
+            decks.insert((battle_match.id, user_id), cards);
+        }
+    }
+
+    Ok(())
+}
+
+async fn previous_deck(
+    txn: &mut PgConnection,
+    match_set_id: &uuid::Uuid,
+    user_id: &uuid::Uuid,
+    deck_size: usize,
+) -> Result<Vec<(String, String)>, AppError> {
+    let cards = sqlx::query_as::<_, (String, String)>(
+        r#"
+        SELECT name, skill
+        FROM battle_cards
+        WHERE user_id = ($2) AND match_set_id = (
+            SELECT bc.match_set_id
+            FROM battle_cards bc
+            JOIN match_sets ms ON ms.id = bc.match_set_id
+            WHERE bc.user_id = ($2)
+              AND ms.created_at < (SELECT created_at FROM match_sets WHERE id = ($1))
+            ORDER BY ms.created_at DESC
+            LIMIT 1
+        )
+        ORDER BY turn_number
+        "#,
+    )
+    .bind(match_set_id)
+    .bind(user_id)
+    .fetch_all(txn)
+    .await?;
+
+    // The deck size of the section may have shrunk since
+    Ok(cards.into_iter().take(deck_size).collect())
+}
+
+fn random_deck(catalog: &Catalog, deck_size: usize) -> Vec<(String, String)> {
+    let keys = catalog.keys();
+    let mut rng = rand::thread_rng();
+
+    (0..deck_size)
+        .filter_map(|_| keys.choose(&mut rng).map(|&key| key.clone()))
+        .collect()
+}
+
+fn section_deck(default_deck: &[DeckCard], deck_size: usize) -> Vec<(String, String)> {
+    default_deck
+        .iter()
+        .take(deck_size)
+        .map(|card| (card.name.clone(), card.skill.clone()))
+        .collect()
+}
+
+async fn insert_filled_deck(
+    txn: &mut PgConnection,
+    match_set_id: &uuid::Uuid,
+    user_id: &uuid::Uuid,
+    cards: &[(String, String)],
+) -> Result<(), AppError> {
+    for (i, (name, skill)) in cards.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO battle_cards (name, skill, user_id, turn_number, match_set_id)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(name)
+        .bind(skill)
+        .bind(user_id)
+        .bind(i as i16 + 1)
+        .bind(match_set_id)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn record_policy(
+    txn: &mut PgConnection,
+    match_set_id: &uuid::Uuid,
+    user_id: &uuid::Uuid,
+    policy: MissingDeckPolicy,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE match_sets
+        SET
+            user1_deck_policy = CASE WHEN user1_id = ($2) THEN ($3) ELSE user1_deck_policy END,
+            user2_deck_policy = CASE WHEN user2_id = ($2) THEN ($3) ELSE user2_deck_policy END
+        WHERE id = ($1)
+        "#,
+    )
+    .bind(match_set_id)
+    .bind(user_id)
+    .bind(policy.as_str())
+    .execute(txn)
+    .await?;
+
+    Ok(())
+}
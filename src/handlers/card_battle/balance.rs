@@ -0,0 +1,125 @@
+use axum::{extract, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use crate::error::AppError;
+
+// Every filter is optional, the set range and dates are inclusive
+#[derive(Debug, Deserialize)]
+pub struct BalanceQuery {
+    section: Option<String>,
+    set_from: Option<i32>,
+    set_to: Option<i32>,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CardBalance {
+    name: String,
+    skill: String,
+    picks: i64,
+    // Share of all the cards students submitted
+    pick_rate: Option<f64>,
+    plays: i64,
+    // Share of the plays that hit, None for cards that never deal damage
+    hit_rate: Option<f64>,
+    average_damage: Option<f64>,
+    // Share of the plays that cancelled a strike, None if the card is not a block
+    cancel_rate: Option<f64>,
+    // Simulated decks containing the card at least once
+    decks: i64,
+    win_rate: Option<f64>,
+}
+
+// For admin
+// Real match numbers for rebalancing the catalog, complements the simulated analysis
+pub async fn get_card_balance(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Query(query): extract::Query<BalanceQuery>,
+) -> Result<axum::Json<Vec<CardBalance>>, AppError> {
+    let balance = sqlx::query_as::<_, CardBalance>(
+        r#"
+        WITH FilteredMatches AS (
+            SELECT *
+            FROM match_sets
+            WHERE (($1)::TEXT IS NULL OR section = ($1))
+              AND (($2)::INT IS NULL OR set >= ($2))
+              AND (($3)::INT IS NULL OR set <= ($3))
+              AND (($4)::TIMESTAMPTZ IS NULL OR created_at >= ($4))
+              AND (($5)::TIMESTAMPTZ IS NULL OR created_at <= ($5))
+        ), SubmittedDecks AS (
+            -- Only the decks students built, not the bot's or the autofilled ones
+            SELECT fm.id AS match_set_id, d.user_id, d.verdict
+            FROM FilteredMatches fm
+            CROSS JOIN LATERAL (
+                VALUES
+                    (fm.user1_id, fm.user1_battle_verdict, fm.user1_deck_policy),
+                    (fm.user2_id, fm.user2_battle_verdict, fm.user2_deck_policy)
+            ) AS d (user_id, verdict, deck_policy)
+            JOIN users u ON u.id = d.user_id
+            WHERE u.role <> 'bot' AND d.deck_policy IS NULL
+        ), Picks AS (
+            SELECT bc.name, bc.skill, COUNT(*) AS picks
+            FROM battle_cards bc
+            JOIN SubmittedDecks sd ON sd.match_set_id = bc.match_set_id AND sd.user_id = bc.user_id
+            GROUP BY bc.name, bc.skill
+        ), Plays AS (
+            SELECT
+                -- Blocks are named after their catalog card with a _block suffix
+                CASE WHEN h.card_skill = 'block' THEN LEFT(h.card_name, -6) ELSE h.card_name END AS name,
+                h.card_skill AS skill,
+                COUNT(*) AS plays,
+                COUNT(*) FILTER (WHERE h.is_hit) AS hits,
+                AVG(h.damage) AS average_damage,
+                COUNT(*) FILTER (WHERE h.interaction = 'cancel') AS cancels
+            FROM card_battle_history h
+            JOIN SubmittedDecks sd ON sd.match_set_id = h.match_set_id AND sd.user_id = h.user_id
+            WHERE h.card_name IS NOT NULL
+            GROUP BY 1, 2
+        ), Decks AS (
+            SELECT DISTINCT
+                bc.name,
+                bc.skill,
+                bc.match_set_id,
+                bc.user_id,
+                sd.verdict
+            FROM battle_cards bc
+            JOIN SubmittedDecks sd ON sd.match_set_id = bc.match_set_id AND sd.user_id = bc.user_id
+        ), DeckResults AS (
+            SELECT
+                name,
+                skill,
+                COUNT(verdict) AS decks,
+                COUNT(*) FILTER (WHERE verdict = 'win') AS wins
+            FROM Decks
+            GROUP BY name, skill
+        )
+        SELECT
+            c.name,
+            c.skill,
+            COALESCE(p.picks, 0) AS picks,
+            COALESCE(p.picks, 0)::FLOAT8 / NULLIF((SELECT SUM(picks) FROM Picks), 0) AS pick_rate,
+            COALESCE(pl.plays, 0) AS plays,
+            CASE WHEN c.skill IN ('strike', 'feint', 'counter') THEN pl.hits::FLOAT8 / NULLIF(pl.plays, 0) END AS hit_rate,
+            pl.average_damage::FLOAT8 AS average_damage,
+            CASE WHEN c.skill = 'block' THEN pl.cancels::FLOAT8 / NULLIF(pl.plays, 0) END AS cancel_rate,
+            COALESCE(dr.decks, 0) AS decks,
+            dr.wins::FLOAT8 / NULLIF(dr.decks, 0) AS win_rate
+        FROM battle_card_catalog c
+        LEFT JOIN Picks p ON p.name = c.name AND p.skill = c.skill
+        LEFT JOIN Plays pl ON pl.name = c.name AND pl.skill = c.skill
+        LEFT JOIN DeckResults dr ON dr.name = c.name AND dr.skill = c.skill
+        ORDER BY c.skill, c.name
+        "#,
+    )
+    .bind(query.section)
+    .bind(query.set_from)
+    .bind(query.set_to)
+    .bind(query.from)
+    .bind(query.to)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(axum::Json(balance))
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use axum::{extract, http, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgPool};
+
+use crate::error::AppError;
+
+use super::model::{Block, Card, Counter, Dodge, Effect, Feint, Strike};
+
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct CatalogCard {
+    name: String,
+    skill: String,
+    damage: f32,
+    accuracy: f32,
+    damage_reduction: f32,
+    strike_to_cancel: Option<String>,
+    reflect: f32,
+    crit_chance: f32,
+    crit_multiplier: f32,
+    damage_spread: f32,
+    effect: Json<Effect>,
+    version: i32,
+    updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl CatalogCard {
+    fn to_card(&self) -> Option<Card> {
+        match self.skill.as_str() {
+            "strike" => Some(Card::Strike(Strike {
+                name: self.name.clone(),
+                damage: self.damage,
+                accuracy: self.accuracy,
+                effect: self.effect.0.clone(),
+                crit_chance: self.crit_chance,
+                crit_multiplier: self.crit_multiplier,
+                damage_spread: self.damage_spread,
+            })),
+            "block" => Some(Card::Block(Block {
+                name: format!("{}_block", self.name),
+                damage_reduction: self.damage_reduction,
+                strike_to_cancel: self.strike_to_cancel.clone().unwrap_or_default(),
+                effect: self.effect.0.clone(),
+            })),
+            "dodge" => Some(Card::Dodge(Dodge {
+                name: self.name.clone(),
+                effect: self.effect.0.clone(),
+            })),
+            "feint" => Some(Card::Feint(Feint {
+                name: self.name.clone(),
+                damage: self.damage,
+                accuracy: self.accuracy,
+                effect: self.effect.0.clone(),
+            })),
+            "counter" => Some(Card::Counter(Counter {
+                name: self.name.clone(),
+                reflect: self.reflect,
+                effect: self.effect.0.clone(),
+            })),
+            _ => None,
+        }
+    }
+}
+
+// Loaded before every simulation so edits from the admin apply without a redeploy
+pub struct Catalog {
+    cards: HashMap<(String, String), Card>,
+}
+
+impl Catalog {
+    pub async fn fetch(pool: &PgPool) -> Result<Self, AppError> {
+        let catalog_cards = sqlx::query_as::<_, CatalogCard>("SELECT * FROM battle_card_catalog")
+            .fetch_all(pool)
+            .await?;
+
+        let cards = catalog_cards
+            .iter()
+            .filter_map(|card| {
+                card.to_card()
+                    .map(|battle_card| ((card.name.clone(), card.skill.clone()), battle_card))
+            })
+            .collect();
+
+        Ok(Catalog { cards })
+    }
+
+    pub fn get(&self, name: &str, skill: &str) -> Option<&Card> {
+        self.cards.get(&(name.to_string(), skill.to_string()))
+    }
+
+    // Sorted so picking from them does not depend on the order of the map
+    pub fn keys(&self) -> Vec<&(String, String)> {
+        let mut keys: Vec<_> = self.cards.keys().collect();
+        keys.sort();
+
+        keys
+    }
+}
+
+pub async fn get_catalog(
+    extract::State(pool): extract::State<PgPool>,
+) -> Result<axum::Json<Vec<CatalogCard>>, AppError> {
+    let catalog = sqlx::query_as::<_, CatalogCard>(
+        "SELECT * FROM battle_card_catalog ORDER BY skill DESC, name",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(axum::Json(catalog))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCatalogCard {
+    name: String,
+    skill: String, // "strike", "block", "dodge", "feint" or "counter"
+    damage: Option<f32>,
+    accuracy: Option<f32>,
+    damage_reduction: Option<f32>,
+    strike_to_cancel: Option<String>,
+    reflect: Option<f32>,
+    crit_chance: Option<f32>,
+    crit_multiplier: Option<f32>,
+    damage_spread: Option<f32>,
+    effect: Effect,
+}
+
+// For admin
+pub async fn insert_catalog_card(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<CreateCatalogCard>,
+) -> Result<(http::StatusCode, axum::Json<CatalogCard>), AppError> {
+    let card = sqlx::query_as::<_, CatalogCard>(
+        r#"
+        INSERT INTO battle_card_catalog (
+            name,
+            skill,
+            damage,
+            accuracy,
+            damage_reduction,
+            strike_to_cancel,
+            reflect,
+            effect,
+            crit_chance,
+            crit_multiplier,
+            damage_spread
+        )
+        VALUES (
+            $1,
+            $2,
+            COALESCE($3, 0),
+            COALESCE($4, 0),
+            COALESCE($5, 0),
+            $6,
+            COALESCE($7, 0),
+            $8,
+            COALESCE($9, 0),
+            COALESCE($10, 1.5),
+            COALESCE($11, 0)
+        )
+        RETURNING *
+        "#,
+    )
+    .bind(payload.name)
+    .bind(payload.skill)
+    .bind(payload.damage)
+    .bind(payload.accuracy)
+    .bind(payload.damage_reduction)
+    .bind(payload.strike_to_cancel)
+    .bind(payload.reflect)
+    .bind(Json(payload.effect))
+    .bind(payload.crit_chance)
+    .bind(payload.crit_multiplier)
+    .bind(payload.damage_spread)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok((http::StatusCode::CREATED, axum::Json(card)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCatalogCard {
+    damage: Option<f32>,
+    accuracy: Option<f32>,
+    damage_reduction: Option<f32>,
+    strike_to_cancel: Option<String>,
+    reflect: Option<f32>,
+    crit_chance: Option<f32>,
+    crit_multiplier: Option<f32>,
+    damage_spread: Option<f32>,
+    effect: Option<Effect>,
+}
+
+// For admin
+// Every edit bumps the version so balance changes can be tracked
+pub async fn update_catalog_card(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path((skill, name)): extract::Path<(String, String)>,
+    extract::Json(payload): extract::Json<UpdateCatalogCard>,
+) -> Result<axum::Json<CatalogCard>, AppError> {
+    let card = sqlx::query_as::<_, CatalogCard>(
+        r#"
+        UPDATE battle_card_catalog
+        SET
+            damage = COALESCE($3, damage),
+            accuracy = COALESCE($4, accuracy),
+            damage_reduction = COALESCE($5, damage_reduction),
+            strike_to_cancel = COALESCE(NULLIF($6, ''), strike_to_cancel),
+            reflect = COALESCE($7, reflect),
+            effect = COALESCE($8, effect),
+            crit_chance = COALESCE($9, crit_chance),
+            crit_multiplier = COALESCE($10, crit_multiplier),
+            damage_spread = COALESCE($11, damage_spread),
+            version = version + 1,
+            updated_at = NOW()
+        WHERE name = ($1) AND skill = ($2)
+        RETURNING *
+        "#,
+    )
+    .bind(name)
+    .bind(skill)
+    .bind(payload.damage)
+    .bind(payload.accuracy)
+    .bind(payload.damage_reduction)
+    .bind(payload.strike_to_cancel)
+    .bind(payload.reflect)
+    .bind(payload.effect.map(Json))
+    .bind(payload.crit_chance)
+    .bind(payload.crit_multiplier)
+    .bind(payload.damage_spread)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(axum::Json(card))
+}
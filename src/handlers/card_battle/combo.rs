@@ -0,0 +1,207 @@
+use axum::{extract, http, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgPool};
+
+use crate::error::AppError;
+
+use super::{
+    catalog::Catalog,
+    model::{CardOutcome, DeckCard, Effect, Interaction, PlayerTurn},
+};
+
+// Strikes and blocks played on consecutive turns, the bonus lands on the last one
+// if it lands too: a strike that hits or a block that cancels a strike
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
+pub struct Combo {
+    name: String,
+    sequence: Json<Vec<DeckCard>>,
+    bonus_damage: f32,
+    effect: Option<Json<Effect>>,
+}
+
+impl Combo {
+    pub async fn fetch_all(pool: &PgPool) -> Result<Vec<Self>, AppError> {
+        let combos = sqlx::query_as::<_, Combo>("SELECT * FROM battle_combos ORDER BY name")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(combos)
+    }
+
+    // The turns of a combo can also start the next one
+    fn is_finished_by(&self, user_turns: &[PlayerTurn]) -> bool {
+        let Json(sequence) = &self.sequence;
+
+        if sequence.is_empty() || user_turns.len() < sequence.len() {
+            return false;
+        }
+
+        user_turns[user_turns.len() - sequence.len()..]
+            .iter()
+            .zip(sequence.iter())
+            .all(|(turn, card)| {
+                turn.card_skill.as_deref() == Some(card.skill.as_str())
+                    && turn.card_name.as_deref() == Some(played_name(card).as_str())
+            })
+    }
+}
+
+// Blocks are named after their catalog card with a _block suffix once played
+fn played_name(card: &DeckCard) -> String {
+    match card.skill.as_str() {
+        "block" => format!("{}_block", card.name),
+        _ => card.name.clone(),
+    }
+}
+
+// Fires the longest combo finished by the last of the user's turns, before the power cards
+pub fn apply_combos(combos: &[Combo], user_turns: &mut [PlayerTurn], outcome: &mut CardOutcome) {
+    let Some(combo) = combos
+        .iter()
+        .filter(|combo| combo.is_finished_by(user_turns))
+        .max_by_key(|combo| combo.sequence.len())
+    else {
+        return;
+    };
+
+    let Some(user_turn) = user_turns.last_mut() else {
+        return;
+    };
+
+    let is_landed = match user_turn.card_skill.as_deref() {
+        Some("block") => user_turn.interaction == Some(Interaction::Cancel),
+        _ => user_turn.is_hit,
+    };
+
+    if !is_landed {
+        return;
+    }
+
+    user_turn.combo = Some(combo.name.clone());
+
+    // Blocks deal no damage, so a combo finished by one only gives its effect
+    if user_turn.is_hit && combo.bonus_damage > 0.0 {
+        user_turn.log.combo_damage = combo.bonus_damage;
+        user_turn.damage += combo.bonus_damage;
+        outcome.damage += combo.bonus_damage;
+    }
+
+    outcome.combo_effect = combo.effect.as_ref().map(|Json(effect)| effect.clone());
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvalidCombo {
+    name: String,
+    reason: String,
+}
+
+async fn validate_combos(pool: &PgPool, combos: &[Combo]) -> Result<(), AppError> {
+    let catalog = Catalog::fetch(pool).await?;
+
+    let invalid_combos: Vec<InvalidCombo> = combos
+        .iter()
+        .filter_map(|combo| {
+            let Json(sequence) = &combo.sequence;
+
+            let reason = if sequence.len() < 2 {
+                "A combo needs at least two cards."
+            } else if sequence
+                .iter()
+                .any(|card| card.skill != "strike" && card.skill != "block")
+            {
+                "Only strikes and blocks can be part of a combo."
+            } else if sequence
+                .iter()
+                .any(|card| catalog.get(&card.name, &card.skill).is_none())
+            {
+                "Card not found in the catalog."
+            } else if combo.bonus_damage < 0.0 {
+                "Bonus damage can't be negative."
+            } else if combo.bonus_damage > 0.0
+                && sequence.last().is_some_and(|card| card.skill == "block")
+            {
+                "A combo finished by a block can't deal bonus damage."
+            } else {
+                return None;
+            };
+
+            Some(InvalidCombo {
+                name: combo.name.clone(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect();
+
+    if invalid_combos.is_empty() {
+        return Ok(());
+    }
+
+    Err(AppError::with_details(
+        http::StatusCode::UNPROCESSABLE_ENTITY,
+        "Invalid combos.",
+        serde_json::to_value(invalid_combos)?,
+    ))
+}
+
+pub async fn get_combos(
+    extract::State(pool): extract::State<PgPool>,
+) -> Result<axum::Json<Vec<Combo>>, AppError> {
+    let combos = Combo::fetch_all(&pool).await?;
+
+    Ok(axum::Json(combos))
+}
+
+// For admin
+pub async fn update_combos(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<Vec<Combo>>,
+) -> Result<http::StatusCode, AppError> {
+    validate_combos(&pool, &payload).await?;
+
+    let mut txn = pool.begin().await?;
+
+    for combo in payload.iter() {
+        sqlx::query(
+            r#"
+            INSERT INTO battle_combos (name, sequence, bonus_damage, effect)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (name) DO UPDATE
+            SET
+                sequence = EXCLUDED.sequence,
+                bonus_damage = EXCLUDED.bonus_damage,
+                effect = EXCLUDED.effect
+            "#,
+        )
+        .bind(&combo.name)
+        .bind(&combo.sequence)
+        .bind(combo.bonus_damage)
+        .bind(&combo.effect)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(http::StatusCode::OK)
+}
+
+// For admin
+pub async fn delete_combo(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path(name): extract::Path<String>,
+) -> Result<http::StatusCode, AppError> {
+    let deleted = sqlx::query("DELETE FROM battle_combos WHERE name = ($1)")
+        .bind(name)
+        .execute(&pool)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(AppError::new(
+            http::StatusCode::NOT_FOUND,
+            "Combo not found.",
+        ));
+    }
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
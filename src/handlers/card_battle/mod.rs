@@ -1,31 +1,56 @@
 // NOTE: The code for this is VERY bad. I wrote this a few months ago and copy pasted it.
 
+use std::collections::HashMap;
+
+use anyhow::Context;
 use axum::{extract, http, response::Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{types::Json, FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
 use tracing::{info, warn};
 
-use crate::error::AppError;
+use crate::{error::AppError, handlers::user::UserId};
 
-use self::model::{
-    Block, Card, CreateBattleCard, Effect, PlayerTurn, PlayerTurnResults, Strike, Target,
-    UserStatus,
+use self::{
+    catalog::Catalog,
+    model::{
+        BattleCard, BattleVerdict, Card, CardOutcome, CreateBattleCard, DeckCard, PlayerTurn,
+        PlayerTurnResults, Target, TurnLog, UserStatus,
+    },
+    rules::{BattleRules, PowerCardModifier},
+    run::BattleRun,
+    submission::DeckMatch,
 };
 
-use super::matchmake::MatchQuery;
-
 // pub mod card_battle;
+pub mod analysis;
+pub mod autofill;
+pub mod balance;
+pub mod catalog;
+pub mod combo;
 pub mod model;
-
-const NUMBER_OF_CARDS: usize = 6;
+pub mod reveal;
+pub mod reward;
+pub mod rules;
+pub mod run;
+pub mod submission;
+pub mod summary;
 
 #[derive(Debug, Deserialize, Serialize, FromRow)]
 pub struct CardBattle {
     id: uuid::Uuid,
     card_name: Option<String>,
+    card_skill: Option<String>,
     card_effect: Option<String>,
     damage: f32,
     is_cancelled: bool,
+    is_hit: bool,
+    is_critical: bool,
+    interaction: Option<String>,
+    power_modifier: Option<String>,
+    remaining_hp: Option<f32>,
+    combo: Option<String>,
     turn_number: i32,
     match_set_id: uuid::Uuid,
     user_id: uuid::Uuid,
@@ -45,276 +70,1064 @@ pub async fn get_match_results(
     Ok(axum::Json(card_battle))
 }
 
+#[derive(Debug, Serialize, FromRow)]
+pub struct CardBattleLog {
+    user_id: uuid::Uuid,
+    turn_number: i32,
+    card_name: Option<String>,
+    card_skill: Option<String>,
+    damage: f32,
+    is_cancelled: bool,
+    is_hit: bool,
+    is_critical: bool,
+    interaction: Option<String>,
+    remaining_hp: Option<f32>,
+    combo: Option<String>,
+    log: Option<Json<TurnLog>>,
+}
+
+// Turn by turn state for animated replays and debugging the effects
+pub async fn get_match_log(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path(match_set_id): extract::Path<uuid::Uuid>,
+) -> Result<axum::Json<Vec<CardBattleLog>>, AppError> {
+    let logs = sqlx::query_as::<_, CardBattleLog>(
+        r#"
+        SELECT user_id, turn_number, card_name, card_skill, damage, is_cancelled, is_hit, is_critical, interaction, remaining_hp, combo, log
+        FROM card_battle_history
+        WHERE match_set_id = ($1)
+        ORDER BY turn_number, user_id
+        "#,
+    )
+    .bind(match_set_id)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(axum::Json(logs))
+}
+
+#[derive(Debug, Serialize)]
+pub struct BattleReplay {
+    seed: i64,
+    results: PlayerTurnResults,
+    matches_history: bool,
+}
+
+// Re-runs a simulated match with its stored seed so disputed outcomes can be verified
+pub async fn replay_match(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path(match_set_id): extract::Path<uuid::Uuid>,
+) -> Result<axum::Json<BattleReplay>, AppError> {
+    let (user1_id, user2_id, battle_seed, battle_snapshot) = sqlx::query_as::<
+        _,
+        (
+            uuid::Uuid,
+            uuid::Uuid,
+            Option<i64>,
+            Option<Json<BattleSnapshot>>,
+        ),
+    >(
+        "SELECT user1_id, user2_id, battle_seed, battle_snapshot FROM match_sets WHERE id = ($1)",
+    )
+    .bind(match_set_id)
+    .fetch_one(&pool)
+    .await?;
+
+    // Both are saved in the transaction that simulates the set
+    let (Some(seed), Some(Json(snapshot))) = (battle_seed, battle_snapshot) else {
+        return Err(AppError::new(
+            http::StatusCode::NOT_FOUND,
+            "Match has not been simulated yet.",
+        ));
+    };
+
+    let BattleSnapshot {
+        user1_cards,
+        user2_cards,
+        rules,
+    } = snapshot;
+
+    let (user1_turns, user2_turns) = simulate_match(
+        (&user1_cards, &user2_cards),
+        seed,
+        (&user1_id, &user2_id),
+        &rules,
+    )?;
+
+    let history = sqlx::query_as::<_, CardBattle>(
+        "SELECT * FROM card_battle_history WHERE match_set_id = ($1) ORDER BY user_id, turn_number",
+    )
+    .bind(match_set_id)
+    .fetch_all(&pool)
+    .await?;
+
+    let matches_history = is_same_history(&history, &user1_id, &user1_turns)
+        && is_same_history(&history, &user2_id, &user2_turns);
+
+    Ok(axum::Json(BattleReplay {
+        seed,
+        results: PlayerTurnResults {
+            user1: (user1_id, user1_turns),
+            user2: (user2_id, user2_turns),
+        },
+        matches_history,
+    }))
+}
+
+fn is_same_history(history: &[CardBattle], user_id: &uuid::Uuid, turns: &[PlayerTurn]) -> bool {
+    let stored: Vec<&CardBattle> = history.iter().filter(|h| h.user_id == *user_id).collect();
+
+    // Turns are only stored if the user submitted their cards
+    if stored.is_empty() {
+        return !has_history(turns);
+    }
+
+    stored.len() == turns.len()
+        && stored.iter().zip(turns.iter()).all(|(stored, turn)| {
+            stored.card_name == turn.card_name
+                && stored.card_skill == turn.card_skill
+                && stored.card_effect == turn.card_effect
+                && stored.damage == turn.damage
+                && stored.is_cancelled == turn.is_cancelled
+                && stored.is_hit == turn.is_hit
+                && stored.is_critical == turn.is_critical
+                && stored.interaction.as_deref() == turn.interaction.map(|i| i.as_str())
+                && stored.power_modifier == turn.power_modifier
+                && stored.remaining_hp == turn.remaining_hp
+                && stored.combo == turn.combo
+        })
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvalidCard {
+    turn_number: usize,
+    name: String,
+    skill: String,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeckValidation {
+    // None for an empty deck, which has no user to look the match up by
+    expected_cards: Option<usize>,
+    received_cards: usize,
+    invalid_cards: Vec<InvalidCard>,
+}
+
+// Rejects the whole deck so a single typo can't break the card battle of the section
+fn validate_deck(
+    catalog: &Catalog,
+    cards: &[CreateBattleCard],
+    deck_size: usize,
+) -> Result<(), AppError> {
+    let invalid_cards: Vec<InvalidCard> = cards
+        .iter()
+        .enumerate()
+        .filter_map(|(i, card)| {
+            let reason = if catalog.get(&card.name, &card.skill).is_none() {
+                "Unknown battle card."
+            } else if card.user_id != cards[0].user_id {
+                "Card belongs to a different user."
+            } else {
+                return None;
+            };
+
+            Some(InvalidCard {
+                turn_number: i + 1,
+                name: card.name.clone(),
+                skill: card.skill.clone(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect();
+
+    if cards.len() == deck_size && invalid_cards.is_empty() {
+        return Ok(());
+    }
+
+    let validation = DeckValidation {
+        expected_cards: Some(deck_size),
+        received_cards: cards.len(),
+        invalid_cards,
+    };
+
+    Err(AppError::with_details(
+        http::StatusCode::UNPROCESSABLE_ENTITY,
+        "Invalid deck.",
+        serde_json::to_value(validation)?,
+    ))
+}
+
+// Practice decks are not tied to a match, so nothing else limits their length
+const MAX_PRACTICE_DECK_SIZE: usize = 30;
+
+#[derive(Debug, Serialize)]
+pub struct PracticeDeckValidation {
+    max_cards: usize,
+    user1_cards: usize,
+    user2_cards: usize,
+    user1_invalid_cards: Vec<InvalidCard>,
+    user2_invalid_cards: Vec<InvalidCard>,
+}
+
+// Unknown cards are rejected rather than skipped, which would simulate a different deck
+fn validate_practice_decks(
+    catalog: &Catalog,
+    (user1_deck, user2_deck): (&[DeckCard], &[DeckCard]),
+) -> Result<(), AppError> {
+    let invalid_cards = |deck: &[DeckCard]| -> Vec<InvalidCard> {
+        deck.iter()
+            .enumerate()
+            .filter(|(_, card)| catalog.get(&card.name, &card.skill).is_none())
+            .map(|(i, card)| InvalidCard {
+                turn_number: i + 1,
+                name: card.name.clone(),
+                skill: card.skill.clone(),
+                reason: "Unknown battle card.".to_string(),
+            })
+            .collect()
+    };
+
+    let validation = PracticeDeckValidation {
+        max_cards: MAX_PRACTICE_DECK_SIZE,
+        user1_cards: user1_deck.len(),
+        user2_cards: user2_deck.len(),
+        user1_invalid_cards: invalid_cards(user1_deck),
+        user2_invalid_cards: invalid_cards(user2_deck),
+    };
+
+    if validation.user1_cards <= MAX_PRACTICE_DECK_SIZE
+        && validation.user2_cards <= MAX_PRACTICE_DECK_SIZE
+        && validation.user1_invalid_cards.is_empty()
+        && validation.user2_invalid_cards.is_empty()
+    {
+        return Ok(());
+    }
+
+    Err(AppError::with_details(
+        http::StatusCode::UNPROCESSABLE_ENTITY,
+        "Invalid deck.",
+        serde_json::to_value(validation)?,
+    ))
+}
+
 pub async fn insert_cards(
     extract::State(pool): extract::State<PgPool>,
     axum::Json(payload): axum::Json<Vec<CreateBattleCard>>,
 ) -> Result<http::StatusCode, AppError> {
+    submit_deck(&pool, payload, false).await?;
+
+    Ok(http::StatusCode::CREATED)
+}
+
+// Swaps the whole submitted deck, allowed until the card deadline
+pub async fn replace_cards(
+    extract::State(pool): extract::State<PgPool>,
+    axum::Json(payload): axum::Json<Vec<CreateBattleCard>>,
+) -> Result<http::StatusCode, AppError> {
+    submit_deck(&pool, payload, true).await?;
+
+    Ok(http::StatusCode::OK)
+}
+
+async fn submit_deck(
+    pool: &PgPool,
+    payload: Vec<CreateBattleCard>,
+    replace: bool,
+) -> Result<(), AppError> {
+    let Some(user_id) = payload.first().map(|card| card.user_id) else {
+        let validation = DeckValidation {
+            expected_cards: None,
+            received_cards: 0,
+            invalid_cards: Vec::new(),
+        };
+
+        return Err(AppError::with_details(
+            http::StatusCode::UNPROCESSABLE_ENTITY,
+            "Invalid deck.",
+            serde_json::to_value(validation)?,
+        ));
+    };
+
+    let deck_match = DeckMatch::fetch(pool, &user_id).await?;
+    deck_match.check_deadline()?;
+
+    let catalog = Catalog::fetch(pool).await?;
+
+    // The deck size is set per section and copied to every match on matchmaking
+    validate_deck(&catalog, &payload, deck_match.deck_size as usize)?;
+
     let mut txn = pool.begin().await?;
 
+    let deleted =
+        sqlx::query("DELETE FROM battle_cards WHERE user_id = ($1) AND match_set_id = ($2)")
+            .bind(user_id)
+            .bind(deck_match.id)
+            .execute(&mut *txn)
+            .await?
+            .rows_affected();
+
+    // Rolled back on drop, a first submission never overwrites a deck
+    if deleted > 0 && !replace {
+        return Err(AppError::new(
+            http::StatusCode::CONFLICT,
+            "Deck already submitted, replace it instead.",
+        ));
+    }
+
     for (i, card) in payload.into_iter().enumerate() {
         sqlx::query(
             r#"
-            WITH LatestMatch AS (
-                SELECT id
-                FROM match_sets
-                WHERE og_user1_id = ($3) OR og_user2_id = ($3)
-                ORDER BY created_at DESC
-                LIMIT 1 
-            )
             INSERT INTO battle_cards (name, skill, user_id, turn_number, match_set_id) 
-            SELECT 
-                ($1) AS name, 
-                ($2) AS skill, 
-                ($3) AS user_id, 
-                ($4) AS turn_number,
-                LatestMatch.id AS match_set_id
-            FROM LatestMatch
-            WHERE NOT EXISTS (
-                SELECT 1
-                FROM battle_cards
-                WHERE user_id = ($3)
-                  AND turn_number = ($4)
-                  AND match_set_id = LatestMatch.id
-            )
+            VALUES ($1, $2, $3, $4, $5)
             "#,
         )
         .bind(card.name)
         .bind(card.skill)
         .bind(card.user_id)
-        // .bind(card.match_set_id)
         .bind(i as i16 + 1)
+        .bind(deck_match.id)
         .execute(&mut *txn)
         .await?;
     }
 
+    submission::record_submission(&mut txn, &deck_match.id, &user_id).await?;
+
     txn.commit().await?;
 
-    Ok(http::StatusCode::CREATED)
+    Ok(())
 }
 
-async fn get_cards(
-    pool: &PgPool,
-    user_id: &uuid::Uuid,
-    match_set_id: &uuid::Uuid,
-) -> Result<Vec<Option<Card>>, AppError> {
-    let battle_cards_res = sqlx::query_as::<_, (String, String)>(
+// Changes a single turn of the submitted deck, allowed until the card deadline
+pub async fn update_card(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path(turn_number): extract::Path<i16>,
+    axum::Json(payload): axum::Json<CreateBattleCard>,
+) -> Result<axum::Json<BattleCard>, AppError> {
+    let deck_match = DeckMatch::fetch(&pool, &payload.user_id).await?;
+    deck_match.check_deadline()?;
+
+    let catalog = Catalog::fetch(&pool).await?;
+
+    if catalog.get(&payload.name, &payload.skill).is_none() {
+        let invalid_card = InvalidCard {
+            turn_number: turn_number as usize,
+            name: payload.name,
+            skill: payload.skill,
+            reason: "Unknown battle card.".to_string(),
+        };
+
+        return Err(AppError::with_details(
+            http::StatusCode::UNPROCESSABLE_ENTITY,
+            "Invalid card.",
+            serde_json::to_value(invalid_card)?,
+        ));
+    }
+
+    let mut txn = pool.begin().await?;
+
+    let battle_card = sqlx::query_as::<_, BattleCard>(
         r#"
-        SELECT name, skill 
-        FROM battle_cards 
-        WHERE user_id = ($1) AND match_set_id = ($2) 
-        ORDER BY turn_number 
-        LIMIT 6
+        UPDATE battle_cards
+        SET name = ($1), skill = ($2)
+        WHERE user_id = ($3) AND match_set_id = ($4) AND turn_number = ($5)
+        RETURNING *
         "#,
     )
-    .bind(user_id)
-    .bind(match_set_id)
-    .fetch_all(pool)
+    .bind(payload.name)
+    .bind(payload.skill)
+    .bind(payload.user_id)
+    .bind(deck_match.id)
+    .bind(turn_number)
+    .fetch_optional(&mut *txn)
+    .await?
+    .ok_or(AppError::new(
+        http::StatusCode::NOT_FOUND,
+        "No submitted card for the turn.",
+    ))?;
+
+    submission::record_submission(&mut txn, &deck_match.id, &payload.user_id).await?;
+
+    txn.commit().await?;
+
+    Ok(axum::Json(battle_card))
+}
+
+// Submitted deck of the user for their latest match
+pub async fn get_deck(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Query(query): extract::Query<UserId>,
+) -> Result<axum::Json<Vec<BattleCard>>, AppError> {
+    let deck_match = DeckMatch::fetch(&pool, &query.user_id).await?;
+
+    let battle_cards = sqlx::query_as::<_, BattleCard>(
+        r#"
+        SELECT *
+        FROM battle_cards
+        WHERE user_id = ($1) AND match_set_id = ($2)
+        ORDER BY turn_number
+        "#,
+    )
+    .bind(query.user_id)
+    .bind(deck_match.id)
+    .fetch_all(&pool)
     .await?;
 
+    Ok(axum::Json(battle_cards))
+}
+
+fn build_deck<'a>(
+    catalog: &Catalog,
+    deck_size: usize,
+    cards: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Vec<Option<Card>> {
     // Default to None since some users may not have submitted their cards
-    let mut battle_cards: Vec<Option<Card>> = vec![None; NUMBER_OF_CARDS];
-
-    for (name, skill) in battle_cards_res.iter() {
-        match skill.as_str() {
-            "strike" => {
-                let strike_card = Strike::new(name.as_str());
-                // Find the first None and replace it with the strike card
-                if let Some(index) = battle_cards.iter().position(|card| card.is_none()) {
-                    battle_cards[index] = Some(Card::Strike(strike_card));
-                }
-            }
-            "block" => {
-                let block_card = Block::new(name.as_str());
-                // Find the first None and replace it with the block card
-                if let Some(index) = battle_cards.iter().position(|card| card.is_none()) {
-                    battle_cards[index] = Some(Card::Block(block_card));
-                }
+    let mut battle_cards: Vec<Option<Card>> = vec![None; deck_size];
+
+    for (battle_card, (name, skill)) in battle_cards.iter_mut().zip(cards) {
+        match catalog.get(name, skill) {
+            Some(card) => {
+                *battle_card = Some(card.clone());
             }
-            _ => {
-                warn!("Unknown skill.");
+            // The turn stays empty so the later cards keep their turn
+            None => {
+                warn!("Unknown battle card: {} ({}).", name, skill);
             }
         }
     }
 
     // println!(">> Battle Cards: {:?}\n", battle_cards);
 
-    Ok(battle_cards)
+    battle_cards
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulateBattle {
+    user1: Vec<DeckCard>,
+    user2: Vec<DeckCard>,
+    seed: Option<i64>,
+    // Modifiers of the week to simulate with, none are applied if left out
+    arnis_skill: Option<String>,
+    arnis_footwork: Option<String>,
+    #[serde(default)]
+    user1_power_cards: Vec<String>,
+    #[serde(default)]
+    user2_power_cards: Vec<String>,
+    hp_pool: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BattleSimulation {
+    seed: i64,
+    results: PlayerTurnResults,
+    user1_total_damage: f32,
+    user2_total_damage: f32,
+    knockout_turn: Option<i16>,
+}
+
+// Dry run for practicing deck building and testing balance changes, nothing is saved
+pub async fn simulate_battle(
+    extract::State(pool): extract::State<PgPool>,
+    axum::Json(payload): axum::Json<SimulateBattle>,
+) -> Result<axum::Json<BattleSimulation>, AppError> {
+    let seed = payload.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+    let catalog = Catalog::fetch(&pool).await?;
+    validate_practice_decks(&catalog, (&payload.user1, &payload.user2))?;
+
+    // Decks that are not tied to a match are as long as the longest of the two
+    let deck_size = payload.user1.len().max(payload.user2.len());
+    let user1_cards = build_deck(
+        &catalog,
+        deck_size,
+        payload.user1.iter().map(DeckCard::as_pair),
+    );
+    let user2_cards = build_deck(
+        &catalog,
+        deck_size,
+        payload.user2.iter().map(DeckCard::as_pair),
+    );
+    let rules = BattleRules::fetch(
+        &pool,
+        payload.arnis_skill.as_deref(),
+        payload.arnis_footwork.as_deref(),
+    )
+    .await?
+    .with_power_cards(
+        &pool,
+        (&payload.user1_power_cards, &payload.user2_power_cards),
+    )
+    .await?
+    .with_hp_pool(payload.hp_pool);
+
+    // Nil ids give user1 the first stream of the seed
+    let users = (&uuid::Uuid::nil(), &uuid::Uuid::nil());
+    let (user1_turns, user2_turns) =
+        simulate_match((&user1_cards, &user2_cards), seed, users, &rules)?;
+
+    Ok(axum::Json(BattleSimulation {
+        seed,
+        knockout_turn: knockout_turn((&user1_turns, &user2_turns)),
+        user1_total_damage: user1_turns.iter().map(|turn| turn.damage).sum(),
+        user2_total_damage: user2_turns.iter().map(|turn| turn.damage).sum(),
+        results: PlayerTurnResults {
+            user1: (uuid::Uuid::nil(), user1_turns),
+            user2: (uuid::Uuid::nil(), user2_turns),
+        },
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BattleRunQuery {
+    set: i32,
+    section: String,
+    rerun: Option<bool>,
 }
 
 // Runs when admin simulates the card battle
+// A completed set is only simulated again if the admin explicitly asks for a re-run
 pub async fn card_battle(
     extract::State(pool): extract::State<PgPool>,
-    extract::Query(query): extract::Query<MatchQuery>,
+    extract::Query(query): extract::Query<BattleRunQuery>,
+) -> Result<axum::Json<BattleRun>, AppError> {
+    let battle_run = run::start_run(
+        &pool,
+        &query.section,
+        query.set,
+        query.rerun.unwrap_or(false),
+    )
+    .await?;
+
+    // Spawned so the run is still finished if the admin's connection drops mid-simulation
+    let task = tokio::spawn(async move {
+        let result = simulate_set(&pool, &battle_run.id, &query.section, query.set).await;
+        let battle_run = run::finish_run(
+            &pool,
+            &battle_run.id,
+            result.as_ref().err().map(AppError::message),
+        )
+        .await?;
+
+        result.map(|()| battle_run)
+    });
+
+    let battle_run = task.await.context("Card battle run was interrupted.")??;
+
+    Ok(axum::Json(battle_run))
+}
+
+#[derive(Debug, FromRow)]
+struct BattleMatch {
+    id: uuid::Uuid,
+    user1_id: uuid::Uuid,
+    user2_id: uuid::Uuid,
+    battle_seed: Option<i64>,
+    arnis_skill: String,
+    arnis_footwork: String,
+    user1_battle_power_cards: Option<Vec<String>>,
+    user2_battle_power_cards: Option<Vec<String>>,
+    deck_size: i16,
+    hp_pool: Option<f32>,
+}
+
+impl BattleMatch {
+    const COLUMNS: &'static str = "id, user1_id, user2_id, battle_seed, arnis_skill, arnis_footwork, user1_battle_power_cards, user2_battle_power_cards, deck_size, hp_pool";
+}
+
+// Cards and rules a match was simulated with, stored so replays don't depend on later edits
+#[derive(Debug, Deserialize, Serialize)]
+struct BattleSnapshot {
+    user1_cards: Vec<Option<Card>>,
+    user2_cards: Vec<Option<Card>>,
+    rules: BattleRules,
+}
+
+// Decks, rules and results of a single match of the set, kept in memory until the whole set is simulated
+struct MatchResult {
+    match_set_id: uuid::Uuid,
+    results: PlayerTurnResults,
+    verdicts: (BattleVerdict, BattleVerdict),
+    snapshot: BattleSnapshot,
+}
+
+// The whole set is simulated in memory first, then saved at once so a failed run leaves nothing behind
+async fn simulate_set(
+    pool: &PgPool,
+    battle_run_id: &uuid::Uuid,
+    section: &str,
+    set: i32,
 ) -> Result<(), AppError> {
     let mut txn = pool.begin().await?;
 
-    let matches = sqlx::query_as::<_, (uuid::Uuid, uuid::Uuid, uuid::Uuid)>(
-        r#"SELECT id, user1_id, user2_id FROM match_sets WHERE set = ($1) AND section = ($2)"#,
-    )
-    .bind(query.set)
-    .bind(query.section)
-    .fetch_all(&mut *txn)
-    .await?;
+    // Held until the commit, so a run taking over a stale one waits for it to end
+    sqlx::query("SELECT id FROM battle_runs WHERE id = ($1) FOR UPDATE")
+        .bind(battle_run_id)
+        .execute(&mut *txn)
+        .await?;
+
+    run::reverse_awards(&mut txn, section, set).await?;
+
+    let matches = fetch_set_matches(&mut txn, section, set).await?;
+    let match_set_ids: Vec<uuid::Uuid> = matches.iter().map(|m| m.id).collect();
+
+    let catalog = Catalog::fetch(pool).await?;
+    let mut decks = fetch_set_decks(&mut txn, &match_set_ids).await?;
+    autofill::fill_missing_decks(&mut txn, &catalog, section, &matches, &mut decks).await?;
+    let power_card_modifiers = PowerCardModifier::fetch_all(pool).await?;
+    // Every match of a set usually shares the week's skill and footwork
+    let mut set_rules: HashMap<(String, String), BattleRules> = HashMap::new();
+
+    let mut match_results = Vec::with_capacity(matches.len());
+
+    for (i, battle_match) in matches.iter().enumerate() {
+        let BattleMatch {
+            id: match_set_id,
+            user1_id,
+            user2_id,
+            battle_seed,
+            arnis_skill,
+            arnis_footwork,
+            user1_battle_power_cards,
+            user2_battle_power_cards,
+            deck_size,
+            hp_pool,
+        } = battle_match;
 
-    for (i, (match_set_id, user1_id, user2_id)) in matches.iter().enumerate() {
         info!("----- MATCH START -----");
         info!("{match_set_id}");
 
-        // Each user can only have 6 cards
-        let user1_cards = get_cards(&pool, user1_id, match_set_id).await?;
-        let user2_cards = get_cards(&pool, user2_id, match_set_id).await?;
+        let seed = battle_seed.ok_or(AppError::new(
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            "Match has no battle seed.",
+        ))?;
+
+        let deck = |user_id: &uuid::Uuid| {
+            let cards = decks
+                .get(&(*match_set_id, *user_id))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            build_deck(
+                &catalog,
+                *deck_size as usize,
+                cards
+                    .iter()
+                    .take(*deck_size as usize)
+                    .map(|(name, skill)| (name.as_str(), skill.as_str())),
+            )
+        };
+        let (user1_cards, user2_cards) = (deck(user1_id), deck(user2_id));
+
+        let rules_key = (arnis_skill.clone(), arnis_footwork.clone());
+        let rules = match set_rules.get(&rules_key) {
+            Some(rules) => rules.clone(),
+            None => {
+                let rules =
+                    BattleRules::fetch(pool, Some(arnis_skill), Some(arnis_footwork)).await?;
+                set_rules.insert(rules_key, rules.clone());
 
-        let mut user1_turns: Vec<PlayerTurn> = vec![PlayerTurn::default(); NUMBER_OF_CARDS];
-        let mut user2_turns: Vec<PlayerTurn> = vec![PlayerTurn::default(); NUMBER_OF_CARDS];
+                rules
+            }
+        }
+        .with_power_card_modifiers(
+            &power_card_modifiers,
+            (
+                user1_battle_power_cards.as_deref().unwrap_or_default(),
+                user2_battle_power_cards.as_deref().unwrap_or_default(),
+            ),
+        )
+        .with_hp_pool(*hp_pool);
 
-        player_turn(
+        let (user1_turns, user2_turns) = simulate_match(
             (&user1_cards, &user2_cards),
-            (&mut user1_turns, &mut user2_turns),
+            seed,
+            (user1_id, user2_id),
+            &rules,
         )?;
 
-        let battle_results = PlayerTurnResults {
+        let verdicts =
+            BattleVerdict::from_match((&user1_cards, &user2_cards), (&user1_turns, &user2_turns));
+
+        let results = PlayerTurnResults {
             user1: (*user1_id, user1_turns),
             user2: (*user2_id, user2_turns),
         };
 
-        process_match_results(&pool, &battle_results, match_set_id).await?;
-        update_total_damage(&pool, match_set_id).await?;
-
         // For debugging purposes
         if i == 0 {
             info!(">> User1: {}\n", user1_id);
-            info!("{:?}\n\n", battle_results.user1);
+            info!("{:?}\n\n", results.user1);
             info!(">> User2: {}\n", user2_id);
-            info!("{:?}\n\n", battle_results.user2);
+            info!("{:?}\n\n", results.user2);
         }
+
+        match_results.push(MatchResult {
+            match_set_id: *match_set_id,
+            results,
+            verdicts,
+            snapshot: BattleSnapshot {
+                user1_cards,
+                user2_cards,
+                rules,
+            },
+        });
     }
 
+    insert_history(&mut txn, &match_results).await?;
+    score_set(&mut txn, &match_results).await?;
+
+    txn.commit().await?;
+
     Ok(())
 }
 
-// Could be improved
-// Could merge query with the query on process_match_results()
-async fn update_total_damage(pool: &PgPool, match_set_id: &uuid::Uuid) -> Result<(), AppError> {
+// Like the seed, the power cards active on the first simulation are kept for replays
+async fn fetch_set_matches(
+    txn: &mut PgConnection,
+    section: &str,
+    set: i32,
+) -> Result<Vec<BattleMatch>, AppError> {
     sqlx::query(
         r#"
-        WITH TotalDamage AS (
-            SELECT
+        UPDATE match_sets ms
+        SET
+            user1_battle_power_cards = ARRAY(
+                SELECT name FROM power_cards
+                WHERE user_id = ms.user1_id AND is_active = TRUE AND is_used = FALSE
+                ORDER BY name
+            ),
+            user2_battle_power_cards = ARRAY(
+                SELECT name FROM power_cards
+                WHERE user_id = ms.user2_id AND is_active = TRUE AND is_used = FALSE
+                ORDER BY name
+            )
+        WHERE set = ($1) AND section = ($2)
+          AND (user1_battle_power_cards IS NULL OR user2_battle_power_cards IS NULL)
+        "#,
+    )
+    .bind(set)
+    .bind(section)
+    .execute(&mut *txn)
+    .await?;
+
+    let mut matches = sqlx::query_as::<_, BattleMatch>(&format!(
+        "SELECT {} FROM match_sets WHERE set = ($1) AND section = ($2)",
+        BattleMatch::COLUMNS
+    ))
+    .bind(set)
+    .bind(section)
+    .fetch_all(&mut *txn)
+    .await?;
+
+    // Keep the first seed so the stored history can always be replayed
+    let (mut seeded_ids, mut seeds) = (Vec::new(), Vec::new());
+
+    for battle_match in matches.iter_mut().filter(|m| m.battle_seed.is_none()) {
+        let seed: i64 = rand::thread_rng().gen();
+
+        battle_match.battle_seed = Some(seed);
+        seeded_ids.push(battle_match.id);
+        seeds.push(seed);
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE match_sets ms
+        SET battle_seed = s.seed
+        FROM UNNEST($1::UUID[], $2::BIGINT[]) AS s(id, seed)
+        WHERE ms.id = s.id
+        "#,
+    )
+    .bind(seeded_ids)
+    .bind(seeds)
+    .execute(&mut *txn)
+    .await?;
+
+    Ok(matches)
+}
+
+// Submitted cards of every user in the set, in turn order
+async fn fetch_set_decks(
+    txn: &mut PgConnection,
+    match_set_ids: &[uuid::Uuid],
+) -> Result<HashMap<(uuid::Uuid, uuid::Uuid), Vec<(String, String)>>, AppError> {
+    let battle_cards = sqlx::query_as::<_, (uuid::Uuid, uuid::Uuid, String, String)>(
+        r#"
+        SELECT match_set_id, user_id, name, skill
+        FROM battle_cards
+        WHERE match_set_id = ANY($1)
+        ORDER BY turn_number
+        "#,
+    )
+    .bind(match_set_ids)
+    .fetch_all(txn)
+    .await?;
+
+    let mut decks: HashMap<_, Vec<_>> = HashMap::new();
+
+    for (match_set_id, user_id, name, skill) in battle_cards {
+        decks
+            .entry((match_set_id, user_id))
+            .or_default()
+            .push((name, skill));
+    }
+
+    Ok(decks)
+}
+
+// Users without a deck have no history, so their total damage stays NULL
+fn has_history(turns: &[PlayerTurn]) -> bool {
+    turns.iter().any(|turn| turn.card_name.is_some())
+}
+
+// Stays under the bind parameter limit of Postgres
+const HISTORY_BATCH_SIZE: usize = 4096;
+
+async fn insert_history(
+    txn: &mut PgConnection,
+    match_results: &[MatchResult],
+) -> Result<(), AppError> {
+    let turns: Vec<_> = match_results
+        .iter()
+        .flat_map(|result| {
+            [&result.results.user1, &result.results.user2]
+                .into_iter()
+                .filter(|(_, turns)| has_history(turns))
+                .flat_map(move |(user_id, turns)| {
+                    turns
+                        .iter()
+                        .enumerate()
+                        .map(move |(i, turn)| (result.match_set_id, *user_id, i as i32 + 1, turn))
+                })
+        })
+        .collect();
+
+    for batch in turns.chunks(HISTORY_BATCH_SIZE) {
+        let mut query_builder = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO card_battle_history (
                 user_id,
+                card_name,
+                card_effect,
+                damage,
+                is_cancelled,
+                turn_number,
+                match_set_id,
+                log,
+                card_skill,
+                interaction,
+                power_modifier,
+                remaining_hp,
+                is_critical,
+                combo,
+                is_hit
+            )
+            "#,
+        );
+
+        query_builder.push_values(
+            batch,
+            |mut row, (match_set_id, user_id, turn_number, turn)| {
+                row.push_bind(user_id)
+                    .push_bind(&turn.card_name)
+                    .push_bind(&turn.card_effect)
+                    .push_bind(turn.damage)
+                    .push_bind(turn.is_cancelled)
+                    .push_bind(turn_number)
+                    .push_bind(match_set_id)
+                    .push_bind(Json(&turn.log))
+                    .push_bind(&turn.card_skill)
+                    .push_bind(turn.interaction.as_ref().map(|i| i.as_str()))
+                    .push_bind(&turn.power_modifier)
+                    .push_bind(turn.remaining_hp)
+                    .push_bind(turn.is_critical)
+                    .push_bind(&turn.combo)
+                    .push_bind(turn.is_hit);
+            },
+        );
+
+        query_builder.build().execute(&mut *txn).await?;
+    }
+
+    Ok(())
+}
+
+// Rewards are configured in the battle_rewards table
+async fn score_set(txn: &mut PgConnection, match_results: &[MatchResult]) -> Result<(), AppError> {
+    let total_damage = |turns: &[PlayerTurn]| {
+        has_history(turns).then(|| turns.iter().map(|turn| turn.damage).sum::<f32>())
+    };
+
+    let match_set_ids: Vec<uuid::Uuid> = match_results.iter().map(|r| r.match_set_id).collect();
+    let user1_total_damage: Vec<Option<f32>> = match_results
+        .iter()
+        .map(|r| total_damage(&r.results.user1.1))
+        .collect();
+    let user2_total_damage: Vec<Option<f32>> = match_results
+        .iter()
+        .map(|r| total_damage(&r.results.user2.1))
+        .collect();
+    let user1_verdicts: Vec<&str> = match_results
+        .iter()
+        .map(|r| r.verdicts.0.as_str())
+        .collect();
+    let user2_verdicts: Vec<&str> = match_results
+        .iter()
+        .map(|r| r.verdicts.1.as_str())
+        .collect();
+    let knockout_turns: Vec<Option<i16>> = match_results
+        .iter()
+        .map(|r| knockout_turn((&r.results.user1.1, &r.results.user2.1)))
+        .collect();
+    let remaining_hp = |turns: &[PlayerTurn]| turns.last().and_then(|turn| turn.remaining_hp);
+    let user1_remaining_hp: Vec<Option<f32>> = match_results
+        .iter()
+        .map(|r| remaining_hp(&r.results.user1.1))
+        .collect();
+    let user2_remaining_hp: Vec<Option<f32>> = match_results
+        .iter()
+        .map(|r| remaining_hp(&r.results.user2.1))
+        .collect();
+    let snapshots: Vec<Json<&BattleSnapshot>> =
+        match_results.iter().map(|r| Json(&r.snapshot)).collect();
+
+    sqlx::query(
+        r#"
+        WITH Results AS (
+            SELECT *
+            FROM UNNEST(
+                $1::UUID[],
+                $2::REAL[],
+                $3::REAL[],
+                $4::TEXT[],
+                $5::TEXT[],
+                $6::SMALLINT[],
+                $7::REAL[],
+                $8::REAL[],
+                $9::JSONB[]
+            ) AS r(
                 match_set_id,
-                SUM(damage) as total_damage 
-            FROM
-                card_battle_history
-            WHERE
-                match_set_id = ($1)
-            GROUP BY
-                user_id, match_set_id
-        ), UpdateTotalDamage AS (
-            UPDATE match_sets
+                user1_total_damage,
+                user2_total_damage,
+                user1_verdict,
+                user2_verdict,
+                knockout_turn,
+                user1_remaining_hp,
+                user2_remaining_hp,
+                battle_snapshot
+            )
+        ), UpdateMatchSets AS (
+            UPDATE match_sets ms
             SET
-                user1_total_damage = (SELECT total_damage FROM TotalDamage WHERE user_id = match_sets.user1_id),
-                user2_total_damage = (SELECT total_damage FROM TotalDamage WHERE user_id = match_sets.user2_id)
-            WHERE
-                match_sets.id = ($1)
-            RETURNING user1_total_damage, user2_total_damage
+                user1_total_damage = r.user1_total_damage,
+                user2_total_damage = r.user2_total_damage,
+                user1_battle_verdict = r.user1_verdict,
+                user2_battle_verdict = r.user2_verdict,
+                knockout_turn = r.knockout_turn,
+                user1_remaining_hp = r.user1_remaining_hp,
+                user2_remaining_hp = r.user2_remaining_hp,
+                battle_snapshot = r.battle_snapshot,
+                -- Awarded scores are kept so a re-run can reverse them, the bot is never scored
+                user1_battle_score = CASE WHEN u1.role = 'bot' THEN 0 ELSE COALESCE((
+                    SELECT CASE WHEN u2.role = 'bot' THEN COALESCE(bot_score, score) ELSE score END
+                    FROM battle_rewards
+                    WHERE verdict = r.user1_verdict
+                ), 0) END,
+                user2_battle_score = CASE WHEN u2.role = 'bot' THEN 0 ELSE COALESCE((
+                    SELECT CASE WHEN u1.role = 'bot' THEN COALESCE(bot_score, score) ELSE score END
+                    FROM battle_rewards
+                    WHERE verdict = r.user2_verdict
+                ), 0) END
+            FROM Results r, users u1, users u2
+            WHERE ms.id = r.match_set_id AND u1.id = ms.user1_id AND u2.id = ms.user2_id
+            RETURNING ms.user1_id, ms.user2_id, ms.user1_battle_score, ms.user2_battle_score
+        ), Awarded AS (
+            SELECT user1_id AS user_id, user1_battle_score AS score FROM UpdateMatchSets
+            UNION ALL
+            SELECT user2_id AS user_id, user2_battle_score AS score FROM UpdateMatchSets
         )
 
-        UPDATE users
-        SET score = score + 10
-        WHERE id = (
-            SELECT
-                CASE WHEN user1_total_damage > user2_total_damage THEN user1_id ELSE user2_id END
-            FROM match_sets
-            WHERE id = ($1)
-        );
+        UPDATE users u
+        SET score = u.score + a.score
+        FROM (SELECT user_id, SUM(score) AS score FROM Awarded GROUP BY user_id) a
+        WHERE u.id = a.user_id
         "#,
     )
-    .bind(match_set_id)
-    .execute(pool)
+    .bind(match_set_ids)
+    .bind(user1_total_damage)
+    .bind(user2_total_damage)
+    .bind(user1_verdicts)
+    .bind(user2_verdicts)
+    .bind(knockout_turns)
+    .bind(user1_remaining_hp)
+    .bind(user2_remaining_hp)
+    .bind(snapshots)
+    .execute(txn)
     .await?;
 
     Ok(())
 }
 
-async fn process_match_results(
-    pool: &PgPool,
-    results: &PlayerTurnResults,
-    match_set_id: &uuid::Uuid,
-) -> Result<(), AppError> {
-    let (user_id, turns) = results.user1.clone();
-    insert_turns(&user_id, turns, match_set_id, pool).await?;
+fn simulate_match(
+    (user1_cards, user2_cards): (&Vec<Option<Card>>, &Vec<Option<Card>>),
+    seed: i64,
+    users: (&uuid::Uuid, &uuid::Uuid),
+    rules: &BattleRules,
+) -> anyhow::Result<(Vec<PlayerTurn>, Vec<PlayerTurn>), AppError> {
+    let mut user1_turns: Vec<PlayerTurn> = vec![PlayerTurn::default(); user1_cards.len()];
+    let mut user2_turns: Vec<PlayerTurn> = vec![PlayerTurn::default(); user2_cards.len()];
+    let (mut user1_rng, mut user2_rng) = player_rngs(seed, users);
 
-    let (user_id, turns) = results.user2.clone();
-    insert_turns(&user_id, turns, match_set_id, pool).await?;
+    player_turn(
+        (user1_cards, user2_cards),
+        (&mut user1_turns, &mut user2_turns),
+        rules,
+        (&mut user1_rng, &mut user2_rng),
+    )?;
 
-    Ok(())
+    Ok((user1_turns, user2_turns))
 }
 
-async fn insert_turns(
-    user_id: &uuid::Uuid,
-    turns: Vec<PlayerTurn>,
-    match_set_id: &uuid::Uuid,
-    pool: &PgPool, // txn: &mut PgConnection,
-) -> Result<(), AppError> {
-    if turns[0].card_name.is_some() {
-        let mut txn = pool.begin().await?;
-
-        let sql = r#"
-        INSERT INTO card_battle_history (
-            user_id,
-            card_name,
-            card_effect,
-            damage,
-            is_cancelled,
-            turn_number,
-            match_set_id
-        )
-        SELECT
-            ($1) AS user_id,
-            ($2) AS card_name,
-            ($3) AS card_effect,
-            ($4) AS damage,
-            ($5) AS is_cancelled,
-            ($6) AS turn_number,
-            ($7) AS match_set_id
-        WHERE NOT EXISTS (
-            SELECT 1
-            FROM card_battle_history
-            WHERE user_id = ($1)
-              AND turn_number = ($6)
-              AND match_set_id = ($7)
-        )
-    "#;
-
-        for (i, turn) in turns.into_iter().enumerate() {
-            sqlx::query(sql)
-                .bind(user_id)
-                .bind(turn.card_name)
-                .bind(turn.card_effect)
-                .bind(turn.damage)
-                .bind(turn.is_cancelled)
-                .bind(i as i32 + 1)
-                .bind(match_set_id)
-                .execute(&mut *txn)
-                .await?;
-        }
+// Each player rolls from their own stream of the match seed, picked by user id, so neither the
+// other player's rolls nor which of them is stored as user1 can change their luck
+fn player_rngs(
+    seed: i64,
+    (user1_id, user2_id): (&uuid::Uuid, &uuid::Uuid),
+) -> (ChaCha8Rng, ChaCha8Rng) {
+    let rng = |stream: u64| {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed as u64);
+        rng.set_stream(stream);
 
-        txn.commit().await?;
-    }
+        rng
+    };
 
-    Ok(())
+    if user1_id <= user2_id {
+        (rng(0), rng(1))
+    } else {
+        (rng(1), rng(0))
+    }
 }
 
 fn player_turn(
     (user1_cards, user2_cards): (&Vec<Option<Card>>, &Vec<Option<Card>>),
     (user1_turns, user2_turns): (&mut Vec<PlayerTurn>, &mut Vec<PlayerTurn>),
+    rules: &BattleRules,
+    (user1_rng, user2_rng): (&mut impl Rng, &mut impl Rng),
 ) -> anyhow::Result<(), AppError> {
     let (mut user1_status, mut user2_status) = (UserStatus::default(), UserStatus::default());
+
+    let apply_rules = |cards: &Vec<Option<Card>>| -> Vec<Option<Card>> {
+        cards
+            .iter()
+            .map(|card| card.as_ref().map(|card| rules.apply(card)))
+            .collect()
+    };
+    let (user1_cards, user2_cards) = (apply_rules(user1_cards), apply_rules(user2_cards));
     // let (mut user1_status_temp, mut user2_status_temp) =
     //     (UserStatus::default(), UserStatus::default());
 
-    let (mut prev_effect1, mut prev_effect2): (Option<Effect>, Option<Effect>) = (None, None);
-
-    for i in 0..NUMBER_OF_CARDS {
+    // Both decks are built with the deck size of the match
+    for i in 0..user1_cards.len() {
         let (user1_current_card, user2_current_card) =
             (user1_cards[i].as_ref(), user2_cards[i].as_ref());
 
@@ -329,84 +1142,99 @@ fn player_turn(
         // user1_status_temp = user1_status.clone();
         // user2_status_temp = user2_status.clone();
 
-        // Change multipliers
-        apply_effects(
-            (&mut user1_status, &mut user2_status),
-            (user1_current_card, user2_current_card),
-        );
+        let statuses_before = (user1_status.multiplier(), user2_status.multiplier());
+        let active_effects = (user1_status.effects.clone(), user2_status.effects.clone());
+
+        // Both cards are resolved against the turn-start statuses, then applied together
+        let mut user1_outcome = user1_current_card
+            .map(|card| {
+                card.simulate(
+                    &user1_status,
+                    &mut user1_turns[i],
+                    user2_current_card,
+                    user1_rng,
+                )
+            })
+            .unwrap_or_default();
+        let mut user2_outcome = user2_current_card
+            .map(|card| {
+                card.simulate(
+                    &user2_status,
+                    &mut user2_turns[i],
+                    user1_current_card,
+                    user2_rng,
+                )
+            })
+            .unwrap_or_default();
+
+        // Counters can only be resolved once the opponent's strike is
+        if let Some(Card::Counter(counter)) = user1_current_card {
+            user1_outcome =
+                counter.reflect(&mut user1_turns[i], user2_current_card, &user2_turns[i]);
+        }
+
+        if let Some(Card::Counter(counter)) = user2_current_card {
+            user2_outcome =
+                counter.reflect(&mut user2_turns[i], user1_current_card, &user1_turns[i]);
+        }
+
+        combo::apply_combos(&rules.combos, &mut user1_turns[..=i], &mut user1_outcome);
+        combo::apply_combos(&rules.combos, &mut user2_turns[..=i], &mut user2_outcome);
 
-        // NOTE: Is there a better way to do this?
         if let Some(card) = user1_current_card {
-            match card {
-                Card::Strike(strike) => {
-                    strike.simulate(
-                        &mut user1_status,
-                        &mut user1_turns[i],
-                        user2_current_card,
-                        &mut user2_status,
-                    )?;
-                }
-                Card::Block(block) => {
-                    block.simulate(
-                        &mut user1_status,
-                        &mut user1_turns[i],
-                        user2_current_card,
-                        &mut user2_turns[i],
-                    )?;
-                }
-            }
+            rules::apply_power_cards(
+                card,
+                &mut user1_turns[i],
+                &mut user1_outcome,
+                (&rules.power_cards.0, &rules.power_cards.1),
+            );
         }
 
         if let Some(card) = user2_current_card {
-            match card {
-                Card::Strike(strike) => {
-                    strike.simulate(
-                        &mut user2_status,
-                        &mut user2_turns[i],
-                        user1_current_card,
-                        &mut user1_status,
-                    )?;
-                }
-                Card::Block(block) => {
-                    block.simulate(
-                        &mut user2_status,
-                        &mut user2_turns[i],
-                        user1_current_card,
-                        &mut user1_turns[i],
-                    )?;
-                }
-            }
+            rules::apply_power_cards(
+                card,
+                &mut user2_turns[i],
+                &mut user2_outcome,
+                (&rules.power_cards.1, &rules.power_cards.0),
+            );
         }
 
         // println!(">>> AFTER\n");
         // println!(">> User 1 Current Status: {:?}\n", user1_status);
         // println!(">> User 2 Current Status: {:?}\n\n", user2_status);
 
-        match prev_effect1 {
-            Some(_) => {
-                // println!("PREVIOUS EFFECT (1): {:?}", effect);
-                user1_status = UserStatus::default();
-                // user1_status.effect = None;
-                // user1_status.multiplier = Multiplier::default();
-                prev_effect1 = None;
-            }
-            None => {
-                prev_effect1 = user1_status.effect.clone();
-                // println!("NEW EFFECT (1)");
-            }
-        }
+        user1_status.damage += user1_outcome.damage;
+        user2_status.damage += user2_outcome.damage;
 
-        match prev_effect2 {
-            Some(_) => {
-                // println!("PREVIOUS EFFECT (2): {:?}", effect);
-                user2_status = UserStatus::default();
-                // user1_status.effect = None;
-                // user1_status.multiplier = Multiplier::default();
-                prev_effect2 = None;
-            }
-            None => {
-                prev_effect2 = user2_status.effect.clone();
-                // println!("NEW EFFECT (2)");
+        // Active effects lose a turn before the newly earned ones are added
+        user1_status.tick();
+        user2_status.tick();
+        receive_effects(&mut user1_status, (&user1_outcome, &user2_outcome));
+        receive_effects(&mut user2_status, (&user2_outcome, &user1_outcome));
+
+        let statuses_after = (user1_status.multiplier(), user2_status.multiplier());
+
+        user1_turns[i].log.record_statuses(
+            statuses_before.clone(),
+            statuses_after.clone(),
+            active_effects.0,
+        );
+        user2_turns[i].log.record_statuses(
+            (statuses_before.1, statuses_before.0),
+            (statuses_after.1, statuses_after.0),
+            active_effects.1,
+        );
+
+        // HP mode ends the match on the turn a player runs out of HP
+        if let Some(hp_pool) = rules.hp_pool {
+            user1_turns[i].remaining_hp = Some(hp_pool - user2_status.damage);
+            user2_turns[i].remaining_hp = Some(hp_pool - user1_status.damage);
+
+            if user1_status.damage >= hp_pool || user2_status.damage >= hp_pool {
+                user1_turns.truncate(i + 1);
+                user2_turns.truncate(i + 1);
+
+                break;
             }
         }
     }
@@ -414,33 +1242,538 @@ fn player_turn(
     Ok(())
 }
 
-fn apply_effect(
+// Turn the match ended on in HP mode, None if both players still had HP after the last turn
+fn knockout_turn((user1_turns, user2_turns): (&[PlayerTurn], &[PlayerTurn])) -> Option<i16> {
+    let is_knocked_out = |turns: &[PlayerTurn]| {
+        turns
+            .last()
+            .and_then(|turn| turn.remaining_hp)
+            .is_some_and(|hp| hp <= 0.0)
+    };
+
+    (is_knocked_out(user1_turns) || is_knocked_out(user2_turns)).then_some(user1_turns.len() as i16)
+}
+
+// Effects the user gave themselves land before the ones from the opponent, so the
+// result does not depend on which player is stored as user1
+fn receive_effects(
     user_status: &mut UserStatus,
-    opponent_status: &mut UserStatus,
-    user_card: Option<&Card>,
+    (user_outcome, opponent_outcome): (&CardOutcome, &CardOutcome),
 ) {
-    if let Some(card) = user_card {
-        match card {
-            Card::Strike(_) | Card::Block(_) => {
-                if let Some(effect) = &user_status.effect {
-                    match effect.target {
-                        Target::Opponent => {
-                            effect.change_stat(&mut opponent_status.multiplier);
-                        }
-                        Target::Owner => {
-                            effect.change_stat(&mut user_status.multiplier);
-                        }
-                    }
-                }
-            }
+    if let Some(effect) = user_outcome.effect.as_ref() {
+        if effect.target == Target::Owner {
+            user_status.add_effect(effect.clone());
+        }
+    }
+
+    if let Some(effect) = user_outcome.combo_effect.as_ref() {
+        if effect.target == Target::Owner {
+            user_status.add_effect(effect.clone());
+        }
+    }
+
+    for effect in [&opponent_outcome.effect, &opponent_outcome.combo_effect]
+        .into_iter()
+        .flatten()
+    {
+        if effect.target == Target::Opponent {
+            user_status.add_effect(effect.clone());
         }
     }
 }
 
-fn apply_effects(
-    (user1_status, user2_status): (&mut UserStatus, &mut UserStatus),
-    (user1_card, user2_card): (Option<&Card>, Option<&Card>),
-) {
-    apply_effect(user1_status, user2_status, user1_card);
-    apply_effect(user2_status, user1_status, user2_card);
+#[cfg(test)]
+mod tests {
+    use super::model::{
+        Block, Change, Counter, Dodge, Effect, Interaction, Multiplier, Stacking, Stat, Strike,
+    };
+    use super::*;
+
+    fn effect(action: Change, stat: Stat, target: Target, duration: u8) -> Effect {
+        Effect {
+            action,
+            amount: 0.2,
+            stat,
+            target,
+            duration,
+            stacking: Stacking::Refresh,
+        }
+    }
+
+    fn strike(name: &str, effect: Effect) -> Option<Card> {
+        Some(Card::Strike(Strike {
+            name: name.to_string(),
+            damage: 10.0,
+            accuracy: 0.8,
+            effect,
+            // Crits and spread make the number of rolls of a turn vary
+            crit_chance: 0.25,
+            crit_multiplier: 1.5,
+            damage_spread: 0.1,
+        }))
+    }
+
+    fn block(name: &str, strike_to_cancel: &str, effect: Effect) -> Option<Card> {
+        Some(Card::Block(Block {
+            name: name.to_string(),
+            damage_reduction: 0.5,
+            strike_to_cancel: strike_to_cancel.to_string(),
+            effect,
+        }))
+    }
+
+    // Always hits for its base damage, with no effect
+    fn accurate_strike(name: &str) -> Option<Card> {
+        Some(Card::Strike(Strike {
+            name: name.to_string(),
+            damage: 10.0,
+            accuracy: 1.0,
+            effect: effect(Change::Increase, Stat::Damage, Target::Owner, 0),
+            crit_chance: 0.0,
+            crit_multiplier: 1.0,
+            damage_spread: 0.0,
+        }))
+    }
+
+    #[test]
+    fn swapping_users_mirrors_the_results() {
+        let deck1 = vec![
+            strike(
+                "head_strike",
+                effect(Change::Decrease, Stat::Damage, Target::Opponent, 2),
+            ),
+            block(
+                "leg_strike_block",
+                "leg_strike",
+                effect(Change::Increase, Stat::Accuracy, Target::Owner, 1),
+            ),
+            strike(
+                "leg_strike",
+                effect(Change::Decrease, Stat::Accuracy, Target::Opponent, 1),
+            ),
+            None,
+            strike(
+                "head_strike",
+                effect(Change::Decrease, Stat::Damage, Target::Opponent, 2),
+            ),
+            block(
+                "head_strike_block",
+                "head_strike",
+                effect(Change::Decrease, Stat::Damage, Target::Opponent, 1),
+            ),
+        ];
+        let deck2 = vec![
+            block(
+                "head_strike_block",
+                "head_strike",
+                effect(Change::Decrease, Stat::Damage, Target::Opponent, 1),
+            ),
+            strike(
+                "leg_strike",
+                effect(Change::Decrease, Stat::Accuracy, Target::Opponent, 1),
+            ),
+            strike(
+                "head_strike",
+                effect(Change::Increase, Stat::Damage, Target::Owner, 2),
+            ),
+            strike(
+                "leg_strike",
+                effect(Change::Decrease, Stat::Accuracy, Target::Opponent, 1),
+            ),
+            strike(
+                "head_strike",
+                effect(Change::Decrease, Stat::Damage, Target::Opponent, 2),
+            ),
+            strike(
+                "leg_strike",
+                effect(Change::Increase, Stat::Accuracy, Target::Owner, 1),
+            ),
+        ];
+
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+        let rules = BattleRules::default();
+        let mut has_hits = false;
+
+        for seed in 0..200 {
+            let (user1_turns, user2_turns) =
+                simulate_match((&deck1, &deck2), seed, (&id1, &id2), &rules).unwrap();
+            let (swapped_user1_turns, swapped_user2_turns) =
+                simulate_match((&deck2, &deck1), seed, (&id2, &id1), &rules).unwrap();
+
+            assert_eq!(user1_turns, swapped_user2_turns, "seed {seed}");
+            assert_eq!(user2_turns, swapped_user1_turns, "seed {seed}");
+            assert!(user2_turns.iter().any(|turn| turn.is_cancelled));
+
+            has_hits |= user1_turns
+                .iter()
+                .chain(&user2_turns)
+                .any(|turn| turn.is_hit);
+        }
+
+        assert!(has_hits);
+    }
+
+    #[test]
+    fn effects_last_their_duration() {
+        let mut status = UserStatus::default();
+
+        status.add_effect(effect(Change::Increase, Stat::Damage, Target::Owner, 2));
+        status.add_effect(effect(Change::Decrease, Stat::Accuracy, Target::Owner, 0));
+
+        assert_eq!(status.effects.len(), 1);
+        assert!(status.multiplier().damage > 1.0);
+
+        status.tick();
+
+        assert_eq!(status.effects[0].remaining_turns, 1);
+        assert!(status.multiplier().damage > 1.0);
+
+        status.tick();
+
+        assert!(status.effects.is_empty());
+        assert_eq!(status.multiplier(), Multiplier::default());
+    }
+
+    #[test]
+    fn refreshed_effects_restart_their_duration() {
+        let mut status = UserStatus::default();
+
+        status.add_effect(effect(Change::Increase, Stat::Damage, Target::Owner, 3));
+        status.add_effect(effect(Change::Decrease, Stat::Damage, Target::Owner, 3));
+        status.tick();
+        status.add_effect(Effect {
+            amount: 0.5,
+            ..effect(Change::Increase, Stat::Damage, Target::Owner, 3)
+        });
+
+        // The decrease is another kind of effect and is left alone
+        assert_eq!(status.effects.len(), 2);
+        assert_eq!(status.effects[0].effect.amount, 0.5);
+        assert_eq!(status.effects[0].remaining_turns, 3);
+        assert_eq!(status.effects[1].remaining_turns, 2);
+    }
+
+    #[test]
+    fn stacked_effects_apply_on_top() {
+        let mut status = UserStatus::default();
+        let stacked = |duration| Effect {
+            stacking: Stacking::Stack,
+            ..effect(Change::Decrease, Stat::Accuracy, Target::Opponent, duration)
+        };
+
+        status.add_effect(stacked(1));
+        status.add_effect(stacked(2));
+
+        assert_eq!(status.effects.len(), 2);
+        assert!((status.multiplier().accuracy - 0.6).abs() < 1e-6);
+
+        status.tick();
+
+        assert_eq!(status.effects.len(), 1);
+        assert!((status.multiplier().accuracy - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ignored_effects_are_dropped_while_active() {
+        let mut status = UserStatus::default();
+        let ignored = |amount| Effect {
+            amount,
+            stacking: Stacking::Ignore,
+            ..effect(Change::Increase, Stat::Accuracy, Target::Owner, 1)
+        };
+
+        status.add_effect(ignored(0.2));
+        status.add_effect(ignored(0.5));
+
+        assert_eq!(status.effects.len(), 1);
+        assert_eq!(status.effects[0].effect.amount, 0.2);
+
+        status.tick();
+        status.add_effect(ignored(0.5));
+
+        assert_eq!(status.effects.len(), 1);
+        assert_eq!(status.effects[0].effect.amount, 0.5);
+    }
+
+    #[test]
+    fn knockouts_end_the_match_early() {
+        let deck1 = vec![accurate_strike("head_strike"); 5];
+        let deck2 = vec![None; 5];
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+        let rules = BattleRules::default().with_hp_pool(Some(25.0));
+
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck1, &deck2), 0, (&id1, &id2), &rules).unwrap();
+
+        assert_eq!(user1_turns.len(), 3);
+        assert_eq!(user2_turns.len(), 3);
+        assert_eq!(
+            user2_turns
+                .iter()
+                .map(|turn| turn.remaining_hp)
+                .collect::<Vec<_>>(),
+            vec![Some(15.0), Some(5.0), Some(-5.0)]
+        );
+        assert!(user1_turns
+            .iter()
+            .all(|turn| turn.remaining_hp == Some(25.0)));
+        assert_eq!(knockout_turn((&user1_turns, &user2_turns)), Some(3));
+    }
+
+    #[test]
+    fn matches_without_a_knockout_play_every_turn() {
+        let deck = vec![accurate_strike("head_strike"); 5];
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+
+        let rules = BattleRules::default().with_hp_pool(Some(60.0));
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck, &deck), 0, (&id1, &id2), &rules).unwrap();
+
+        assert_eq!(user1_turns.len(), 5);
+        assert_eq!(user1_turns[4].remaining_hp, Some(10.0));
+        assert_eq!(knockout_turn((&user1_turns, &user2_turns)), None);
+
+        // Both players running out of HP on the same turn is still a knockout
+        let rules = BattleRules::default().with_hp_pool(Some(20.0));
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck, &deck), 0, (&id1, &id2), &rules).unwrap();
+
+        assert_eq!(user2_turns.len(), 2);
+        assert_eq!(knockout_turn((&user1_turns, &user2_turns)), Some(2));
+
+        // Without an HP pool the remaining HP is not tracked
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck, &deck), 0, (&id1, &id2), &BattleRules::default()).unwrap();
+
+        assert_eq!(user1_turns.len(), 5);
+        assert!(user1_turns.iter().all(|turn| turn.remaining_hp.is_none()));
+        assert_eq!(knockout_turn((&user1_turns, &user2_turns)), None);
+    }
+
+    #[test]
+    fn counters_reflect_strikes_that_hit() {
+        let counter = Some(Card::Counter(Counter {
+            name: "counter".to_string(),
+            reflect: 0.5,
+            effect: effect(Change::Increase, Stat::Damage, Target::Owner, 0),
+        }));
+        let deck1 = vec![
+            accurate_strike("head_strike"),
+            block(
+                "head_strike_block",
+                "head_strike",
+                effect(Change::Increase, Stat::Damage, Target::Owner, 0),
+            ),
+        ];
+        let deck2 = vec![counter.clone(), counter];
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck1, &deck2), 0, (&id1, &id2), &BattleRules::default()).unwrap();
+
+        // The strike still lands in full
+        assert!(user1_turns[0].is_hit);
+        assert_eq!(user1_turns[0].damage, 10.0);
+        assert_eq!(user1_turns[0].interaction, Some(Interaction::Counter));
+        assert!(user2_turns[0].is_hit);
+        assert_eq!(user2_turns[0].damage, 5.0);
+        assert_eq!(user2_turns[0].interaction, Some(Interaction::Counter));
+
+        // Nothing to reflect without a strike
+        assert!(!user2_turns[1].is_hit);
+        assert_eq!(user2_turns[1].damage, 0.0);
+        assert_eq!(user2_turns[1].interaction, None);
+    }
+
+    #[test]
+    fn dodges_avoid_strikes_at_a_cost() {
+        let dodge = Some(Card::Dodge(Dodge {
+            name: "dodge".to_string(),
+            effect: effect(Change::Decrease, Stat::Accuracy, Target::Owner, 1),
+        }));
+        let deck1 = vec![accurate_strike("head_strike"), None];
+        let deck2 = vec![dodge.clone(), dodge];
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck1, &deck2), 0, (&id1, &id2), &BattleRules::default()).unwrap();
+
+        assert!(!user1_turns[0].is_hit);
+        assert_eq!(user1_turns[0].damage, 0.0);
+        assert_eq!(user1_turns[0].interaction, Some(Interaction::Dodge));
+        assert_eq!(user2_turns[0].interaction, Some(Interaction::Dodge));
+
+        // The cost applies whether or not there was a strike to avoid
+        for turn in &user2_turns {
+            assert!(turn.card_effect.is_some());
+            assert!(turn.log.user_after.accuracy < 1.0);
+        }
+
+        assert_eq!(user2_turns[1].interaction, None);
+    }
+
+    #[test]
+    fn combos_add_their_bonus_on_the_last_card() {
+        let combos: Vec<combo::Combo> = serde_json::from_value(serde_json::json!([
+            {
+                "name": "one_two",
+                "sequence": [
+                    { "name": "head_strike", "skill": "strike" },
+                    { "name": "leg_strike", "skill": "strike" }
+                ],
+                "bonus_damage": 5.0,
+                "effect": {
+                    "action": "Decrease",
+                    "amount": 0.2,
+                    "stat": "Damage",
+                    "target": "Opponent"
+                }
+            },
+            {
+                "name": "three_hit",
+                "sequence": [
+                    { "name": "head_strike", "skill": "strike" },
+                    { "name": "leg_strike", "skill": "strike" },
+                    { "name": "head_strike", "skill": "strike" }
+                ],
+                "bonus_damage": 8.0,
+                "effect": null
+            },
+            {
+                "name": "parry_riposte",
+                "sequence": [
+                    { "name": "leg_strike", "skill": "block" },
+                    { "name": "head_strike", "skill": "strike" }
+                ],
+                "bonus_damage": 2.0,
+                "effect": null
+            }
+        ]))
+        .unwrap();
+        let deck1 = vec![
+            accurate_strike("head_strike"),
+            accurate_strike("leg_strike"),
+            accurate_strike("head_strike"),
+            accurate_strike("leg_strike"),
+        ];
+        let deck2 = vec![
+            None,
+            None,
+            block(
+                "leg_strike_block",
+                "leg_strike",
+                effect(Change::Increase, Stat::Damage, Target::Owner, 0),
+            ),
+            accurate_strike("head_strike"),
+        ];
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+        let mut rules = BattleRules::default();
+        rules.combos = combos;
+
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck1, &deck2), 0, (&id1, &id2), &rules).unwrap();
+
+        let combos = |turns: &[PlayerTurn]| -> Vec<Option<String>> {
+            turns.iter().map(|turn| turn.combo.clone()).collect()
+        };
+        let damage =
+            |turns: &[PlayerTurn]| -> Vec<f32> { turns.iter().map(|turn| turn.damage).collect() };
+
+        // The longest combo wins, and the turns of a combo can start the next one
+        assert_eq!(
+            combos(&user1_turns),
+            vec![
+                None,
+                Some("one_two".to_string()),
+                Some("three_hit".to_string()),
+                Some("one_two".to_string())
+            ]
+        );
+        // The third strike is halved by the block before its bonus is added
+        assert_eq!(damage(&user1_turns), vec![10.0, 15.0, 13.0, 15.0]);
+        assert_eq!(user1_turns[1].log.combo_damage, 5.0);
+
+        // Blocks of a combo are matched by the strike they are named after
+        assert_eq!(
+            combos(&user2_turns),
+            vec![None, None, None, Some("parry_riposte".to_string())]
+        );
+        assert_eq!(damage(&user2_turns), vec![0.0, 0.0, 0.0, 12.0]);
+
+        // The effect of the combo lands like the effect of a card
+        assert!(user2_turns[1].log.user_after.damage < 1.0);
+        assert_eq!(user2_turns[2].log.user_after, Multiplier::default());
+    }
+
+    #[test]
+    fn combos_only_fire_when_the_last_card_lands() {
+        let combos: Vec<combo::Combo> = serde_json::from_value(serde_json::json!([
+            {
+                "name": "one_two",
+                "sequence": [
+                    { "name": "head_strike", "skill": "strike" },
+                    { "name": "leg_strike", "skill": "strike" }
+                ],
+                "bonus_damage": 5.0,
+                "effect": {
+                    "action": "Decrease",
+                    "amount": 0.2,
+                    "stat": "Damage",
+                    "target": "Opponent"
+                }
+            },
+            {
+                "name": "strike_guard",
+                "sequence": [
+                    { "name": "head_strike", "skill": "strike" },
+                    { "name": "leg_strike", "skill": "block" }
+                ],
+                "bonus_damage": 0.0,
+                "effect": {
+                    "action": "Increase",
+                    "amount": 0.2,
+                    "stat": "Accuracy",
+                    "target": "Owner"
+                }
+            }
+        ]))
+        .unwrap();
+        let leg_strike_block = || {
+            block(
+                "leg_strike_block",
+                "leg_strike",
+                effect(Change::Increase, Stat::Damage, Target::Owner, 0),
+            )
+        };
+        let deck1 = vec![
+            accurate_strike("head_strike"),
+            accurate_strike("leg_strike"),
+            accurate_strike("head_strike"),
+            accurate_strike("head_strike"),
+        ];
+        let deck2 = vec![
+            accurate_strike("head_strike"),
+            leg_strike_block(),
+            accurate_strike("head_strike"),
+            leg_strike_block(),
+        ];
+        let (id1, id2) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+        let mut rules = BattleRules::default();
+        rules.combos = combos;
+
+        let (user1_turns, user2_turns) =
+            simulate_match((&deck1, &deck2), 0, (&id1, &id2), &rules).unwrap();
+
+        // A cancelled finisher gives neither the bonus nor the effect
+        assert!(user1_turns.iter().all(|turn| turn.combo.is_none()));
+        assert_eq!(user1_turns[1].damage, 0.0);
+        assert_eq!(user1_turns[1].log.combo_damage, 0.0);
+        assert_eq!(user2_turns[1].log.user_after.damage, 1.0);
+
+        // A block finishes a combo by cancelling its strike, not by being played
+        assert_eq!(user2_turns[1].combo, Some("strike_guard".to_string()));
+        assert!(user2_turns[1].log.user_after.accuracy > 1.0);
+        assert_eq!(user2_turns[3].combo, None);
+        assert_eq!(user2_turns[3].log.user_after, Multiplier::default());
+    }
 }
@@ -1,10 +1,7 @@
-use axum::http;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 
-use crate::error::AppError;
-
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum Change {
     Increase,
@@ -21,12 +18,77 @@ pub enum Stat {
 pub enum Card {
     Strike(Strike),
     Block(Block),
+    Dodge(Dodge),
+    Feint(Feint),
+    Counter(Counter),
 }
 
 impl Card {
     // pub fn apply_effects() {
 
     // }
+
+    // Resolves the card against the turn-start state, neither status is changed here
+    pub fn simulate(
+        &self,
+        user_status: &UserStatus,
+        user_turn: &mut PlayerTurn,
+        opponent_card: Option<&Card>,
+        rng: &mut impl Rng,
+    ) -> CardOutcome {
+        user_turn.card_skill = Some(self.skill().to_string());
+
+        match self {
+            Card::Strike(strike) => strike.simulate(user_status, user_turn, opponent_card, rng),
+            Card::Block(block) => block.simulate(user_turn, opponent_card),
+            Card::Dodge(dodge) => dodge.simulate(user_turn, opponent_card),
+            Card::Feint(feint) => feint.simulate(user_status, user_turn, opponent_card, rng),
+            // Needs the opponent's strike resolved first, see Counter::reflect()
+            Card::Counter(counter) => {
+                user_turn.card_name = Some(counter.name.clone());
+                CardOutcome::default()
+            }
+        }
+    }
+
+    pub fn base_damage(&self) -> f32 {
+        match self {
+            Card::Strike(strike) => strike.damage,
+            Card::Feint(feint) => feint.damage,
+            Card::Block(_) | Card::Dodge(_) | Card::Counter(_) => 0.0,
+        }
+    }
+
+    pub fn skill(&self) -> &'static str {
+        match self {
+            Card::Strike(_) => "strike",
+            Card::Block(_) => "block",
+            Card::Dodge(_) => "dodge",
+            Card::Feint(_) => "feint",
+            Card::Counter(_) => "counter",
+        }
+    }
+}
+
+// What happened between the two cards of a turn, recorded on both players' turns
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interaction {
+    Cancel,  // A block cancelled the strike
+    Dodge,   // A dodge avoided the strike
+    Feint,   // A feint baited the block
+    Counter, // A counter reflected the strike
+}
+
+impl Interaction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interaction::Cancel => "cancel",
+            Interaction::Dodge => "dodge",
+            Interaction::Feint => "feint",
+            Interaction::Counter => "counter",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -35,15 +97,37 @@ pub enum Target {
     Opponent,
 }
 
+// How a new effect interacts with an active effect on the same stat and direction
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum Stacking {
+    #[default]
+    Refresh, // Replaces the active effect and restarts its duration
+    Stack,  // Applies on top of the active effect
+    Ignore, // Dropped while the active effect lasts
+}
+
 #[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
 pub struct Effect {
     pub action: Change,
     pub amount: f32,
     pub stat: Stat,
     pub target: Target,
+    // Number of turns the effect lasts, starting from the turn after it was earned
+    #[serde(default = "Effect::default_duration")]
+    pub duration: u8,
+    #[serde(default)]
+    pub stacking: Stacking,
 }
 
 impl Effect {
+    fn default_duration() -> u8 {
+        1
+    }
+
+    pub fn is_same_kind(&self, other: &Effect) -> bool {
+        self.stat == other.stat && self.action == other.action
+    }
+
     pub fn change_stat(&self, multiplier: &mut Multiplier) {
         match self.stat {
             Stat::Accuracy => match self.action {
@@ -66,18 +150,12 @@ impl Effect {
     }
 
     pub fn summarize(&self) -> String {
-        let mut target: String;
-
-        match self.target {
-            Target::Owner => {
-                target = "user".into();
-            }
-            Target::Opponent => {
-                target = "opponent".into();
-            }
-        }
+        let target = match self.target {
+            Target::Owner => "user",
+            Target::Opponent => "opponent",
+        };
 
-        match self.stat {
+        let summary = match self.stat {
             Stat::Accuracy => match self.action {
                 Change::Decrease => {
                     format!("Decrease {} accuracy by {:.2}%", target, self.amount)
@@ -94,332 +172,285 @@ impl Effect {
                     format!("Increase {} damage by {:.2}%", target, self.amount)
                 }
             },
+        };
+
+        match self.duration {
+            0 | 1 => summary,
+            turns => format!("{} for {} turns", summary, turns),
         }
     }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
-pub struct StrikeStat {
+pub struct Strike {
     pub name: String,
     pub damage: f32,
     pub accuracy: f32,
     pub effect: Effect,
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
-pub enum Strike {
-    LegStrike(StrikeStat),
-    TempleStrike(StrikeStat),
-    ShoulderStrike(StrikeStat),
-    ShoulderThrust(StrikeStat),
-    EyePoke(StrikeStat),
-    StomachThrust(StrikeStat),
-    HeadStrike(StrikeStat),
-    Unknown,
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+    // Hits deal up to this share more or less damage, 0 for a fixed damage
+    pub damage_spread: f32,
 }
 
 impl Strike {
-    pub fn new(name: &str) -> Self {
-        match name {
-            "leg_strike" => Strike::LegStrike(StrikeStat {
-                name: name.into(),
-                accuracy: 0.9,
-                damage: 5.0,
-                effect: Effect {
-                    action: Change::Increase,
-                    amount: 0.5,
-                    stat: Stat::Accuracy,
-                    target: Target::Owner,
-                },
-            }),
-            "temple_strike" => Strike::TempleStrike(StrikeStat {
-                name: name.into(),
-                accuracy: 0.75,
-                damage: 10.0,
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.5,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            "shoulder_strike" => Strike::ShoulderStrike(StrikeStat {
-                name: name.into(),
-                accuracy: 0.8,
-                damage: 10.0,
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.1,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            "shoulder_thrust" => Strike::ShoulderThrust(StrikeStat {
-                name: name.into(),
-                damage: 8.0,
-                accuracy: 0.85,
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.1,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            "eye_poke" => Strike::EyePoke(StrikeStat {
-                name: name.into(),
-                damage: 12.0,
-                accuracy: 0.6,
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.15,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            "stomach_thrust" => Strike::StomachThrust(StrikeStat {
-                name: name.into(),
-                damage: 10.0,
-                accuracy: 0.85,
-                effect: Effect {
-                    action: Change::Increase,
-                    amount: 0.5,
-                    stat: Stat::Damage,
-                    target: Target::Owner,
-                },
-            }),
-            "head_strike" => Strike::HeadStrike(StrikeStat {
-                name: name.into(),
-                damage: 18.0,
-                accuracy: 0.5,
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.15,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            _ => Strike::Unknown,
-        }
-    }
-
     pub fn simulate(
         &self,
-        user_status: &mut UserStatus,
+        user_status: &UserStatus,
         user_turn: &mut PlayerTurn,
         opponent_card: Option<&Card>,
-        opponent_status: &mut UserStatus,
-    ) -> anyhow::Result<(), AppError> {
-        match self {
-            Strike::LegStrike(strike_stat)
-            | Strike::TempleStrike(strike_stat)
-            | Strike::ShoulderStrike(strike_stat)
-            | Strike::ShoulderThrust(strike_stat)
-            | Strike::EyePoke(strike_stat)
-            | Strike::StomachThrust(strike_stat)
-            | Strike::HeadStrike(strike_stat) => {
-                user_turn.card_name = Some(strike_stat.name.clone());
-                opponent_status.damage_reduction = 0.0;
-                user_status.damage_reduction = 0.0;
-
-                let mut is_cancelled = false;
-
-                if let Some(Card::Block(block)) = opponent_card {
-                    if let Ok(block_stat) = block.get_stat() {
-                        is_cancelled = *self == block_stat.strike_to_cancel;
-                        opponent_status.damage_reduction = block_stat.damage_reduction;
-                    }
+        rng: &mut impl Rng,
+    ) -> CardOutcome {
+        user_turn.card_name = Some(self.name.clone());
+
+        let mut damage_reduction = 0.0;
+
+        match opponent_card {
+            Some(Card::Block(block)) => {
+                user_turn.is_cancelled = self.is_cancelled(block);
+                damage_reduction = block.damage_reduction;
+
+                if user_turn.is_cancelled {
+                    user_turn.interaction = Some(Interaction::Cancel);
                 }
+            }
+            Some(Card::Dodge(_)) => {
+                user_turn.interaction = Some(Interaction::Dodge);
+                user_turn.damage = 0.0;
 
-                let rng: f32 = rand::thread_rng().gen_range(0.0..1.0);
-                let accuracy = strike_stat.accuracy * user_status.multiplier.accuracy;
-                let damage = (strike_stat.damage
-                    * (user_status.multiplier.damage - opponent_status.damage_reduction));
+                return CardOutcome::default();
+            }
+            _ => {}
+        }
 
-                if rng <= accuracy && is_cancelled == false {
-                    user_status.damage += damage;
-                    user_status.effect = Some(strike_stat.effect.clone());
-                    user_turn.card_effect = Some(strike_stat.effect.summarize());
+        let roll: f32 = rng.gen_range(0.0..1.0);
+        let multiplier = user_status.multiplier();
+        let accuracy = self.accuracy * multiplier.accuracy;
+        let damage = self.damage * (multiplier.damage - damage_reduction);
 
-                    // println!("\nCURRENT DAMAGE: {}\n", damage);
+        user_turn.log.roll = Some(roll);
+        user_turn.log.accuracy = Some(accuracy);
+        user_turn.log.damage_reduction = damage_reduction;
 
-                    user_turn.damage = damage;
-                } else {
-                    // println!("\nMISSED!\n");
-                    user_turn.damage = 0.0;
-                }
+        if roll <= accuracy && !user_turn.is_cancelled {
+            let damage = self.roll_damage(damage, user_turn, rng);
+
+            user_turn.card_effect = Some(if user_turn.is_critical {
+                format!("{} (critical hit)", self.effect.summarize())
+            } else {
+                self.effect.summarize()
+            });
+            user_turn.damage = damage;
+            user_turn.is_hit = true;
+
+            if let Some(Card::Counter(_)) = opponent_card {
+                user_turn.interaction = Some(Interaction::Counter);
+            }
+
+            CardOutcome {
+                damage,
+                effect: Some(self.effect.clone()),
+                ..Default::default()
+            }
+        } else {
+            user_turn.damage = 0.0;
+
+            CardOutcome::default()
+        }
+    }
+
+    // Only cards with a spread or a crit chance roll for them, so the seeds of
+    // matches simulated before they were added still give the same results
+    fn roll_damage(&self, damage: f32, user_turn: &mut PlayerTurn, rng: &mut impl Rng) -> f32 {
+        let mut damage = damage;
+
+        if self.damage_spread > 0.0 {
+            let spread_roll: f32 = rng.gen_range(-1.0..=1.0);
 
-                Ok(())
+            user_turn.log.spread_roll = Some(spread_roll);
+            damage *= 1.0 + self.damage_spread * spread_roll;
+        }
+
+        if self.crit_chance > 0.0 {
+            let crit_roll: f32 = rng.gen_range(0.0..1.0);
+
+            user_turn.log.crit_roll = Some(crit_roll);
+
+            if crit_roll < self.crit_chance {
+                user_turn.is_critical = true;
+                damage *= self.crit_multiplier;
             }
-            Strike::Unknown => Err(AppError::new(
-                http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Unknown battle card.",
-            )),
         }
+
+        damage
     }
 
-    pub fn is_cancelled(&self, strike_to_cancel: Strike) -> bool {
-        *self == strike_to_cancel
+    pub fn is_cancelled(&self, block: &Block) -> bool {
+        self.name == block.strike_to_cancel
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct BlockStat {
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Block {
     pub name: String,
     pub damage_reduction: f32,
-    pub strike_to_cancel: Strike,
+    pub strike_to_cancel: String,
     pub effect: Effect,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub enum Block {
-    LegStrike(BlockStat),
-    TempleStrike(BlockStat),
-    ShoulderStrike(BlockStat),
-    ShoulderThrust(BlockStat),
-    EyePoke(BlockStat),
-    StomachThrust(BlockStat),
-    HeadStrike(BlockStat),
-    Unknown,
+impl Block {
+    pub fn simulate(
+        &self,
+        user_turn: &mut PlayerTurn,
+        opponent_card: Option<&Card>,
+    ) -> CardOutcome {
+        user_turn.card_name = Some(self.name.clone());
+
+        match opponent_card {
+            Some(Card::Strike(strike)) if strike.is_cancelled(self) => {
+                user_turn.card_effect = Some(self.effect.summarize());
+                user_turn.interaction = Some(Interaction::Cancel);
+
+                CardOutcome {
+                    damage: 0.0,
+                    effect: Some(self.effect.clone()),
+                    ..Default::default()
+                }
+            }
+            Some(Card::Feint(_)) => {
+                user_turn.interaction = Some(Interaction::Feint);
+
+                CardOutcome::default()
+            }
+            _ => CardOutcome::default(),
+        }
+    }
 }
 
-impl Block {
-    pub fn new(name: &str) -> Self {
-        match name {
-            "leg_strike" => Block::LegStrike(BlockStat {
-                name: "leg_strike_block".into(),
-                damage_reduction: 0.1,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Increase,
-                    amount: 0.1,
-                    stat: Stat::Accuracy,
-                    target: Target::Owner,
-                },
-            }),
-            "temple_strike" => Block::TempleStrike(BlockStat {
-                name: "temple_strike_block".into(),
-                damage_reduction: 0.15,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.1,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            "shoulder_strike" => Block::ShoulderStrike(BlockStat {
-                name: "shoulder_strike_block".into(),
-                damage_reduction: 0.15,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Increase,
-                    amount: 0.5,
-                    stat: Stat::Damage,
-                    target: Target::Owner,
-                },
-            }),
-            "shoulder_thrust" => Block::ShoulderThrust(BlockStat {
-                name: "shoulder_thrust_block".into(),
-                damage_reduction: 0.15,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.1,
-                    stat: Stat::Accuracy,
-                    target: Target::Opponent,
-                },
-            }),
-            "eye_poke" => Block::EyePoke(BlockStat {
-                name: "eye_poke_block".into(),
-                damage_reduction: 0.15,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.1,
-                    stat: Stat::Damage,
-                    target: Target::Opponent,
-                },
-            }),
-            "stomach_thrust" => Block::StomachThrust(BlockStat {
-                name: "stomach_thrust_block".into(),
-                damage_reduction: 0.15,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Increase,
-                    amount: 0.5,
-                    stat: Stat::Damage,
-                    target: Target::Owner,
-                },
-            }),
-            "head_strike" => Block::HeadStrike(BlockStat {
-                name: "head_strike_block".into(),
-                damage_reduction: 0.15,
-                strike_to_cancel: Strike::new(name),
-                effect: Effect {
-                    action: Change::Decrease,
-                    amount: 0.2,
-                    stat: Stat::Damage,
-                    target: Target::Opponent,
-                },
-            }),
-            _ => Block::Unknown,
+// Avoids any strike, the effect is the cost of dodging and always applies
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Dodge {
+    pub name: String,
+    pub effect: Effect,
+}
+
+impl Dodge {
+    pub fn simulate(
+        &self,
+        user_turn: &mut PlayerTurn,
+        opponent_card: Option<&Card>,
+    ) -> CardOutcome {
+        user_turn.card_name = Some(self.name.clone());
+        user_turn.card_effect = Some(self.effect.summarize());
+
+        if let Some(Card::Strike(_)) = opponent_card {
+            user_turn.interaction = Some(Interaction::Dodge);
+        }
+
+        CardOutcome {
+            damage: 0.0,
+            effect: Some(self.effect.clone()),
+            ..Default::default()
         }
     }
+}
 
+// Only lands against a block, ignoring its damage reduction
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Feint {
+    pub name: String,
+    pub damage: f32,
+    pub accuracy: f32,
+    pub effect: Effect,
+}
+
+impl Feint {
     pub fn simulate(
         &self,
-        user_status: &mut UserStatus,
+        user_status: &UserStatus,
         user_turn: &mut PlayerTurn,
         opponent_card: Option<&Card>,
-        opponent_turn: &mut PlayerTurn,
-    ) -> anyhow::Result<(), AppError> {
-        match self {
-            Block::LegStrike(block_stat)
-            | Block::TempleStrike(block_stat)
-            | Block::ShoulderStrike(block_stat)
-            | Block::ShoulderThrust(block_stat)
-            | Block::EyePoke(block_stat)
-            | Block::StomachThrust(block_stat)
-            | Block::HeadStrike(block_stat) => {
-                user_status.damage_reduction = block_stat.damage_reduction;
-                user_turn.card_name = Some(block_stat.name.clone());
-
-                if let Some(Card::Strike(strike)) = opponent_card {
-                    opponent_turn.is_cancelled =
-                        strike.is_cancelled(block_stat.strike_to_cancel.clone());
-                }
+        rng: &mut impl Rng,
+    ) -> CardOutcome {
+        user_turn.card_name = Some(self.name.clone());
 
-                if opponent_turn.is_cancelled {
-                    user_status.effect = Some(block_stat.effect.clone());
-                    user_turn.card_effect = Some(block_stat.effect.summarize());
-                }
+        let Some(Card::Block(_)) = opponent_card else {
+            return CardOutcome::default();
+        };
+
+        user_turn.interaction = Some(Interaction::Feint);
 
-                Ok(())
+        let roll: f32 = rng.gen_range(0.0..1.0);
+        let multiplier = user_status.multiplier();
+        let accuracy = self.accuracy * multiplier.accuracy;
+        let damage = self.damage * multiplier.damage;
+
+        user_turn.log.roll = Some(roll);
+        user_turn.log.accuracy = Some(accuracy);
+
+        if roll <= accuracy {
+            user_turn.card_effect = Some(self.effect.summarize());
+            user_turn.damage = damage;
+            user_turn.is_hit = true;
+
+            CardOutcome {
+                damage,
+                effect: Some(self.effect.clone()),
+                ..Default::default()
             }
-            Block::Unknown => Err(AppError::new(
-                http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Unknown block battle card.",
-            )),
+        } else {
+            CardOutcome::default()
         }
     }
+}
 
-    pub fn get_stat(&self) -> anyhow::Result<BlockStat, AppError> {
-        match self {
-            Block::LegStrike(block_stat)
-            | Block::TempleStrike(block_stat)
-            | Block::ShoulderStrike(block_stat)
-            | Block::ShoulderThrust(block_stat)
-            | Block::EyePoke(block_stat)
-            | Block::StomachThrust(block_stat)
-            | Block::HeadStrike(block_stat) => Ok(block_stat.clone()),
-            Block::Unknown => Err(AppError::new(
-                http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Unknown block battle card.",
-            )),
+// Sends back part of the damage of a strike that hits
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Counter {
+    pub name: String,
+    pub reflect: f32,
+    pub effect: Effect,
+}
+
+impl Counter {
+    pub fn reflect(
+        &self,
+        user_turn: &mut PlayerTurn,
+        opponent_card: Option<&Card>,
+        opponent_turn: &PlayerTurn,
+    ) -> CardOutcome {
+        let Some(Card::Strike(_)) = opponent_card else {
+            return CardOutcome::default();
+        };
+
+        if !opponent_turn.is_hit {
+            return CardOutcome::default();
+        }
+
+        let damage = opponent_turn.damage * self.reflect;
+
+        user_turn.card_effect = Some(self.effect.summarize());
+        user_turn.interaction = Some(Interaction::Counter);
+        user_turn.damage = damage;
+        user_turn.is_hit = true;
+
+        CardOutcome {
+            damage,
+            effect: Some(self.effect.clone()),
+            ..Default::default()
         }
     }
 }
 
+// What a card did during a turn, applied to the statuses once both cards are resolved
+#[derive(Debug, Default)]
+pub struct CardOutcome {
+    pub damage: f32,
+    pub effect: Option<Effect>,
+    // Earned by finishing a combo, lands after the card's own effect
+    pub combo_effect: Option<Effect>,
+}
+
 #[derive(Debug, Deserialize, Serialize, FromRow)]
 pub struct BattleCard {
     pub id: uuid::Uuid,
@@ -439,27 +470,145 @@ pub struct CreateBattleCard {
     // pub turn_number: i16,
 }
 
+// Same shape as CreateBattleCard, used for decks that are not tied to a match
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeckCard {
+    pub name: String,
+    pub skill: String,
+}
+
+impl DeckCard {
+    pub fn as_pair(&self) -> (&str, &str) {
+        (self.name.as_str(), self.skill.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BattleVerdict {
+    Win,
+    Lose,
+    Draw,
+    Forfeit, // Did not submit their cards
+}
+
+impl BattleVerdict {
+    pub fn new(
+        (user_forfeited, opponent_forfeited): (bool, bool),
+        (user_damage, opponent_damage): (f32, f32),
+    ) -> Self {
+        if user_forfeited {
+            BattleVerdict::Forfeit
+        } else if opponent_forfeited || user_damage > opponent_damage {
+            BattleVerdict::Win
+        } else if user_damage < opponent_damage {
+            BattleVerdict::Lose
+        } else {
+            BattleVerdict::Draw
+        }
+    }
+
+    pub fn from_match(
+        (user1_cards, user2_cards): (&[Option<Card>], &[Option<Card>]),
+        (user1_turns, user2_turns): (&[PlayerTurn], &[PlayerTurn]),
+    ) -> (Self, Self) {
+        let forfeited = (
+            user1_cards.iter().all(Option::is_none),
+            user2_cards.iter().all(Option::is_none),
+        );
+        let damage = (
+            user1_turns.iter().map(|turn| turn.damage).sum::<f32>(),
+            user2_turns.iter().map(|turn| turn.damage).sum::<f32>(),
+        );
+
+        (
+            BattleVerdict::new(forfeited, damage),
+            BattleVerdict::new((forfeited.1, forfeited.0), (damage.1, damage.0)),
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BattleVerdict::Win => "win",
+            BattleVerdict::Lose => "lose",
+            BattleVerdict::Draw => "draw",
+            BattleVerdict::Forfeit => "forfeit",
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PlayerTurnResults {
     pub user1: (uuid::Uuid, Vec<PlayerTurn>),
     pub user2: (uuid::Uuid, Vec<PlayerTurn>),
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct PlayerTurn {
     pub card_name: Option<String>,
+    pub card_skill: Option<String>,
     pub card_effect: Option<String>,
     pub damage: f32,
     pub is_cancelled: bool,
+    pub is_hit: bool,
+    pub is_critical: bool,
+    pub interaction: Option<Interaction>,
+    pub power_modifier: Option<String>,
+    pub remaining_hp: Option<f32>, // Only in HP mode
+    pub combo: Option<String>,
+    pub log: TurnLog,
+}
+
+// Full state of a turn, the multipliers are recorded for both the user and their opponent
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct TurnLog {
+    pub user_before: Multiplier,
+    pub user_after: Multiplier,
+    pub opponent_before: Multiplier,
+    pub opponent_after: Multiplier,
+    // Damage reduction from the opponent's block applied to the user's strike
+    pub damage_reduction: f32,
+    pub roll: Option<f32>,
+    pub accuracy: Option<f32>,
+    pub spread_roll: Option<f32>,
+    pub crit_roll: Option<f32>,
+    // Bonus damage of the combo finished by the turn's hit, missing from older logs
+    #[serde(default)]
+    pub combo_damage: f32,
+    // Effects that were active on the user during the turn
+    pub active_effects: Vec<StatusEffect>,
+}
+
+impl TurnLog {
+    pub fn record_statuses(
+        &mut self,
+        (user_before, opponent_before): (Multiplier, Multiplier),
+        (user_after, opponent_after): (Multiplier, Multiplier),
+        active_effects: Vec<StatusEffect>,
+    ) {
+        self.user_before = user_before;
+        self.user_after = user_after;
+        self.opponent_before = opponent_before;
+        self.opponent_after = opponent_after;
+        self.active_effects = active_effects;
+    }
 }
 
 impl Default for PlayerTurn {
     fn default() -> Self {
         PlayerTurn {
             card_name: None,
+            card_skill: None,
             card_effect: None,
             damage: 0.0,
             is_cancelled: false,
+            is_hit: false,
+            is_critical: false,
+            interaction: None,
+            power_modifier: None,
+            remaining_hp: None,
+            combo: None,
+            log: TurnLog::default(),
         }
     }
 }
@@ -479,21 +628,67 @@ impl Default for Multiplier {
     }
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StatusEffect {
+    pub effect: Effect,
+    pub remaining_turns: u8,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct UserStatus {
     pub damage: f32,
-    pub multiplier: Multiplier,
-    pub damage_reduction: f32,
-    pub effect: Option<Effect>,
+    pub effects: Vec<StatusEffect>,
+}
+
+impl UserStatus {
+    pub fn multiplier(&self) -> Multiplier {
+        let mut multiplier = Multiplier::default();
+
+        for status_effect in self.effects.iter() {
+            status_effect.effect.change_stat(&mut multiplier);
+        }
+
+        multiplier
+    }
+
+    pub fn add_effect(&mut self, effect: Effect) {
+        if effect.duration == 0 {
+            return;
+        }
+
+        let active = self
+            .effects
+            .iter()
+            .position(|status_effect| status_effect.effect.is_same_kind(&effect));
+
+        let status_effect = StatusEffect {
+            remaining_turns: effect.duration,
+            effect,
+        };
+
+        match (active, status_effect.effect.stacking) {
+            (Some(_), Stacking::Ignore) => {}
+            (Some(index), Stacking::Refresh) => self.effects[index] = status_effect,
+            (Some(_), Stacking::Stack) | (None, _) => self.effects.push(status_effect),
+        }
+    }
+
+    // Called at the end of every turn, drops the effects that have run out
+    pub fn tick(&mut self) {
+        for status_effect in self.effects.iter_mut() {
+            status_effect.remaining_turns -= 1;
+        }
+
+        self.effects
+            .retain(|status_effect| status_effect.remaining_turns > 0);
+    }
 }
 
 impl Default for UserStatus {
     fn default() -> Self {
         UserStatus {
             damage: 0.0,
-            multiplier: Multiplier::default(),
-            damage_reduction: 0.0,
-            effect: None,
+            effects: Vec::new(),
         }
     }
 }
@@ -0,0 +1,230 @@
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use axum::{
+    extract, http,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Result,
+    },
+};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use crate::{error::AppError, handlers::matchmake::MatchQuery};
+
+use super::{run::BattleRun, CardBattle};
+
+#[derive(Debug, Deserialize)]
+pub struct RevealQuery {
+    set: i32,
+    section: String,
+    // Keeps the previous pacing if not given
+    turn_interval_ms: Option<i32>,
+}
+
+// For admin
+// Starts the reveal of a completed set, starting it again replays it from the first turn
+pub async fn start_reveal(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Query(query): extract::Query<RevealQuery>,
+) -> Result<axum::Json<BattleRun>, AppError> {
+    if query.turn_interval_ms.is_some_and(|interval| interval < 0) {
+        return Err(AppError::new(
+            http::StatusCode::UNPROCESSABLE_ENTITY,
+            "Turn interval can't be negative.",
+        ));
+    }
+
+    let battle_run = sqlx::query_as::<_, BattleRun>(
+        r#"
+        UPDATE battle_runs
+        SET
+            reveal_started_at = NOW(),
+            turn_interval_ms = COALESCE($3, turn_interval_ms)
+        WHERE section = ($1) AND set = ($2) AND status = 'completed'
+        RETURNING *
+        "#,
+    )
+    .bind(&query.section)
+    .bind(query.set)
+    .bind(query.turn_interval_ms)
+    .fetch_optional(&pool)
+    .await?;
+
+    if let Some(battle_run) = battle_run {
+        return Ok(axum::Json(battle_run));
+    }
+
+    let is_run = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM battle_runs WHERE section = ($1) AND set = ($2))",
+    )
+    .bind(&query.section)
+    .bind(query.set)
+    .fetch_one(&pool)
+    .await?;
+
+    if !is_run {
+        return Err(AppError::new(
+            http::StatusCode::NOT_FOUND,
+            "The card battle of this set has not been run yet.",
+        ));
+    }
+
+    Err(AppError::new(
+        http::StatusCode::CONFLICT,
+        "The card battle of this set has not been completed yet.",
+    ))
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RevealMatch {
+    match_set_id: uuid::Uuid,
+    user1_id: uuid::Uuid,
+    user2_id: uuid::Uuid,
+    user1_total_damage: Option<f32>,
+    user2_total_damage: Option<f32>,
+    user1_battle_verdict: Option<String>,
+    user2_battle_verdict: Option<String>,
+    knockout_turn: Option<i16>,
+}
+
+// Both cards of a turn are revealed together, None for a player who forfeited
+#[derive(Debug, Serialize)]
+pub struct RevealTurn<'a> {
+    match_set_id: uuid::Uuid,
+    turn_number: i32,
+    user1: Option<&'a CardBattle>,
+    user2: Option<&'a CardBattle>,
+}
+
+// For the class screen
+// Streams the matches of a revealed set turn by turn, paced from the start of the reveal
+// so a client that reconnects first receives every turn already revealed, then the rest.
+// Events are "turn", then "match_end" after each match, and "done" once the set is revealed.
+pub async fn reveal_battle_run(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Query(query): extract::Query<MatchQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let battle_run = sqlx::query_as::<_, BattleRun>(
+        "SELECT * FROM battle_runs WHERE section = ($1) AND set = ($2)",
+    )
+    .bind(&query.section)
+    .bind(query.set)
+    .fetch_optional(&pool)
+    .await?;
+
+    let Some((reveal_started_at, turn_interval_ms)) =
+        battle_run.and_then(|run| run.reveal_started_at.map(|at| (at, run.turn_interval_ms)))
+    else {
+        return Err(AppError::new(
+            http::StatusCode::NOT_FOUND,
+            "The reveal of this set has not started yet.",
+        ));
+    };
+
+    let matches = sqlx::query_as::<_, RevealMatch>(
+        r#"
+        SELECT
+            id AS match_set_id,
+            user1_id,
+            user2_id,
+            user1_total_damage,
+            user2_total_damage,
+            user1_battle_verdict,
+            user2_battle_verdict,
+            knockout_turn
+        FROM match_sets
+        WHERE section = ($1) AND set = ($2)
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(&query.section)
+    .bind(query.set)
+    .fetch_all(&pool)
+    .await?;
+
+    let history = sqlx::query_as::<_, CardBattle>(
+        r#"
+        SELECT h.*
+        FROM card_battle_history h
+        JOIN match_sets ms ON ms.id = h.match_set_id
+        WHERE ms.section = ($1) AND ms.set = ($2)
+        ORDER BY h.turn_number
+        "#,
+    )
+    .bind(&query.section)
+    .bind(query.set)
+    .fetch_all(&pool)
+    .await?;
+
+    let events = reveal_events(&matches, &history)?;
+
+    // Turns that are already due are sent right away
+    let now = tokio::time::Instant::now();
+    let elapsed = (chrono::Utc::now() - reveal_started_at)
+        .to_std()
+        .unwrap_or_default();
+    let interval = Duration::from_millis(turn_interval_ms as u64);
+
+    let stream = stream::iter(events.into_iter().enumerate()).then(move |(i, event)| async move {
+        let due = interval * i as u32;
+        tokio::time::sleep_until(now + due.saturating_sub(elapsed)).await;
+
+        Ok(event.id(i.to_string()))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn reveal_events(matches: &[RevealMatch], history: &[CardBattle]) -> Result<Vec<Event>, AppError> {
+    let mut match_history: HashMap<uuid::Uuid, Vec<&CardBattle>> = HashMap::new();
+
+    for turn in history {
+        match_history
+            .entry(turn.match_set_id)
+            .or_default()
+            .push(turn);
+    }
+
+    let mut events = Vec::new();
+
+    for reveal_match in matches {
+        let turns = match_history
+            .get(&reveal_match.match_set_id)
+            .cloned()
+            .unwrap_or_default();
+        let find_turn = |user_id: &uuid::Uuid, turn_number: i32| {
+            turns
+                .iter()
+                .find(|turn| turn.user_id == *user_id && turn.turn_number == turn_number)
+                .copied()
+        };
+        let last_turn = turns.iter().map(|turn| turn.turn_number).max().unwrap_or(0);
+
+        for turn_number in 1..=last_turn {
+            let reveal_turn = RevealTurn {
+                match_set_id: reveal_match.match_set_id,
+                turn_number,
+                user1: find_turn(&reveal_match.user1_id, turn_number),
+                user2: find_turn(&reveal_match.user2_id, turn_number),
+            };
+
+            events.push(
+                Event::default()
+                    .event("turn")
+                    .data(serde_json::to_string(&reveal_turn)?),
+            );
+        }
+
+        events.push(
+            Event::default()
+                .event("match_end")
+                .data(serde_json::to_string(reveal_match)?),
+        );
+    }
+
+    events.push(Event::default().event("done").data(""));
+
+    Ok(events)
+}
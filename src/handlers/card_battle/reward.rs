@@ -0,0 +1,60 @@
+use axum::{extract, http, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use crate::error::AppError;
+
+use super::model::BattleVerdict;
+
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct BattleReward {
+    verdict: String,
+    score: i32,
+    bot_score: Option<i32>, // Score on a match against the bot, same as score if None
+}
+
+pub async fn get_rewards(
+    extract::State(pool): extract::State<PgPool>,
+) -> Result<axum::Json<Vec<BattleReward>>, AppError> {
+    let rewards =
+        sqlx::query_as::<_, BattleReward>("SELECT * FROM battle_rewards ORDER BY score DESC")
+            .fetch_all(&pool)
+            .await?;
+
+    Ok(axum::Json(rewards))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateReward {
+    verdict: BattleVerdict,
+    score: i32,
+    bot_score: Option<i32>,
+}
+
+// For admin
+pub async fn update_rewards(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<Vec<UpdateReward>>,
+) -> Result<http::StatusCode, AppError> {
+    let mut txn = pool.begin().await?;
+
+    for reward in payload.iter() {
+        sqlx::query(
+            r#"
+            INSERT INTO battle_rewards (verdict, score, bot_score)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (verdict) DO UPDATE
+            SET score = EXCLUDED.score, bot_score = EXCLUDED.bot_score
+            "#,
+        )
+        .bind(reward.verdict.as_str())
+        .bind(reward.score)
+        .bind(reward.bot_score)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(http::StatusCode::OK)
+}
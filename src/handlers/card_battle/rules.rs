@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use axum::{extract, http, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use crate::error::AppError;
+
+use super::{
+    combo::Combo,
+    model::{Card, CardOutcome, PlayerTurn},
+};
+
+// Modifiers of a match, the arnis ones apply to both players and the power cards to their owner
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct BattleRules {
+    // Accuracy added to the strikes practiced with the week's arnis skill
+    strike_accuracy: HashMap<String, f32>,
+    // Added to the damage reduction of every block, depends on the week's footwork
+    block_damage_reduction: f32,
+    pub power_cards: (PowerCards, PowerCards),
+    // HP every player starts with, the match ends early on a knockout
+    pub hp_pool: Option<f32>,
+    pub combos: Vec<Combo>,
+}
+
+// Active power cards of a player, a card counts once for every copy
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PowerCards {
+    modifiers: Vec<PowerCardModifier>,
+}
+
+impl PowerCards {
+    fn from_modifiers(modifiers: &[PowerCardModifier], names: &[String]) -> Self {
+        let modifiers = names
+            .iter()
+            .filter_map(|name| modifiers.iter().find(|modifier| modifier.name == *name))
+            .cloned()
+            .collect();
+
+        PowerCards { modifiers }
+    }
+
+    fn damage_dealt(&self) -> f32 {
+        self.modifiers.iter().map(|m| m.damage_dealt).product()
+    }
+
+    fn damage_taken(&self) -> f32 {
+        self.modifiers.iter().map(|m| m.damage_taken).product()
+    }
+
+    // Least damage of a hit, as a share of the card's base damage
+    fn damage_floor(&self) -> f32 {
+        self.modifiers
+            .iter()
+            .map(|m| m.damage_floor)
+            .fold(0.0, f32::max)
+    }
+}
+
+impl BattleRules {
+    pub async fn fetch(
+        pool: &PgPool,
+        arnis_skill: Option<&str>,
+        arnis_footwork: Option<&str>,
+    ) -> Result<Self, AppError> {
+        let strike_accuracy = sqlx::query_as::<_, (String, f32)>(
+            "SELECT card_name, accuracy FROM battle_skill_modifiers WHERE arnis_skill = ($1)",
+        )
+        .bind(arnis_skill)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let block_damage_reduction = sqlx::query_scalar::<_, f32>(
+            "SELECT damage_reduction FROM battle_footwork_modifiers WHERE arnis_footwork = ($1)",
+        )
+        .bind(arnis_footwork)
+        .fetch_optional(pool)
+        .await?
+        .unwrap_or_default();
+
+        let combos = Combo::fetch_all(pool).await?;
+
+        Ok(BattleRules {
+            strike_accuracy,
+            block_damage_reduction,
+            combos,
+            ..Default::default()
+        })
+    }
+
+    pub async fn with_power_cards(
+        self,
+        pool: &PgPool,
+        power_cards: (&[String], &[String]),
+    ) -> Result<Self, AppError> {
+        let modifiers = PowerCardModifier::fetch_all(pool).await?;
+
+        Ok(self.with_power_card_modifiers(&modifiers, power_cards))
+    }
+
+    // For simulating many matches without fetching the modifiers for each of them
+    pub fn with_power_card_modifiers(
+        mut self,
+        modifiers: &[PowerCardModifier],
+        (user1_power_cards, user2_power_cards): (&[String], &[String]),
+    ) -> Self {
+        self.power_cards = (
+            PowerCards::from_modifiers(modifiers, user1_power_cards),
+            PowerCards::from_modifiers(modifiers, user2_power_cards),
+        );
+
+        self
+    }
+
+    pub fn with_hp_pool(mut self, hp_pool: Option<f32>) -> Self {
+        self.hp_pool = hp_pool;
+
+        self
+    }
+
+    pub fn apply(&self, card: &Card) -> Card {
+        let mut card = card.clone();
+
+        match &mut card {
+            Card::Strike(strike) => {
+                if let Some(accuracy) = self.strike_accuracy.get(&strike.name) {
+                    strike.accuracy += accuracy;
+                }
+            }
+            Card::Block(block) => {
+                block.damage_reduction =
+                    (block.damage_reduction + self.block_damage_reduction).max(0.0);
+            }
+            Card::Dodge(_) | Card::Feint(_) | Card::Counter(_) => {}
+        }
+
+        card
+    }
+}
+
+// Changes the damage of a hit once both cards of the turn are resolved
+pub fn apply_power_cards(
+    card: &Card,
+    user_turn: &mut PlayerTurn,
+    outcome: &mut CardOutcome,
+    (user_power_cards, opponent_power_cards): (&PowerCards, &PowerCards),
+) {
+    if !user_turn.is_hit {
+        return;
+    }
+
+    let floor = card.base_damage() * user_power_cards.damage_floor();
+    let damage = outcome.damage.max(floor)
+        * user_power_cards.damage_dealt()
+        * opponent_power_cards.damage_taken();
+
+    if damage == outcome.damage {
+        return;
+    }
+
+    // Only the power cards that changed the damage are named
+    let names: Vec<String> = user_power_cards
+        .modifiers
+        .iter()
+        .filter(|m| m.damage_dealt != 1.0 || (m.damage_floor > 0.0 && floor > outcome.damage))
+        .map(|m| m.name.clone())
+        .chain(
+            opponent_power_cards
+                .modifiers
+                .iter()
+                .filter(|m| m.damage_taken != 1.0)
+                .map(|m| format!("{} (opponent)", m.name)),
+        )
+        .collect();
+
+    user_turn.power_modifier = Some(format!(
+        "{}: {:.2} -> {:.2}",
+        names.join(", "),
+        outcome.damage,
+        damage
+    ));
+    user_turn.damage = damage;
+    outcome.damage = damage;
+}
+
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct SkillModifier {
+    arnis_skill: String,
+    card_name: String,
+    accuracy: f32,
+}
+
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct FootworkModifier {
+    arnis_footwork: String,
+    damage_reduction: f32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
+pub struct PowerCardModifier {
+    name: String,
+    damage_dealt: f32,
+    damage_taken: f32,
+    damage_floor: f32,
+}
+
+impl PowerCardModifier {
+    pub async fn fetch_all(pool: &PgPool) -> Result<Vec<Self>, AppError> {
+        let modifiers = sqlx::query_as::<_, PowerCardModifier>(
+            "SELECT * FROM battle_power_card_modifiers ORDER BY name",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(modifiers)
+    }
+}
+
+pub async fn get_skill_modifiers(
+    extract::State(pool): extract::State<PgPool>,
+) -> Result<axum::Json<Vec<SkillModifier>>, AppError> {
+    let modifiers = sqlx::query_as::<_, SkillModifier>(
+        "SELECT * FROM battle_skill_modifiers ORDER BY arnis_skill, card_name",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(axum::Json(modifiers))
+}
+
+// For admin
+pub async fn update_skill_modifiers(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<Vec<SkillModifier>>,
+) -> Result<http::StatusCode, AppError> {
+    let mut txn = pool.begin().await?;
+
+    for modifier in payload.iter() {
+        sqlx::query(
+            r#"
+            INSERT INTO battle_skill_modifiers (arnis_skill, card_name, accuracy)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (arnis_skill, card_name) DO UPDATE SET accuracy = EXCLUDED.accuracy
+            "#,
+        )
+        .bind(&modifier.arnis_skill)
+        .bind(&modifier.card_name)
+        .bind(modifier.accuracy)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(http::StatusCode::OK)
+}
+
+pub async fn get_footwork_modifiers(
+    extract::State(pool): extract::State<PgPool>,
+) -> Result<axum::Json<Vec<FootworkModifier>>, AppError> {
+    let modifiers = sqlx::query_as::<_, FootworkModifier>(
+        "SELECT * FROM battle_footwork_modifiers ORDER BY arnis_footwork",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(axum::Json(modifiers))
+}
+
+// For admin
+pub async fn update_footwork_modifiers(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<Vec<FootworkModifier>>,
+) -> Result<http::StatusCode, AppError> {
+    let mut txn = pool.begin().await?;
+
+    for modifier in payload.iter() {
+        sqlx::query(
+            r#"
+            INSERT INTO battle_footwork_modifiers (arnis_footwork, damage_reduction)
+            VALUES ($1, $2)
+            ON CONFLICT (arnis_footwork) DO UPDATE SET damage_reduction = EXCLUDED.damage_reduction
+            "#,
+        )
+        .bind(&modifier.arnis_footwork)
+        .bind(modifier.damage_reduction)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(http::StatusCode::OK)
+}
+
+pub async fn get_power_card_modifiers(
+    extract::State(pool): extract::State<PgPool>,
+) -> Result<axum::Json<Vec<PowerCardModifier>>, AppError> {
+    let modifiers = PowerCardModifier::fetch_all(&pool).await?;
+
+    Ok(axum::Json(modifiers))
+}
+
+// For admin
+pub async fn update_power_card_modifiers(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<Vec<PowerCardModifier>>,
+) -> Result<http::StatusCode, AppError> {
+    let mut txn = pool.begin().await?;
+
+    for modifier in payload.iter() {
+        sqlx::query(
+            r#"
+            INSERT INTO battle_power_card_modifiers (name, damage_dealt, damage_taken, damage_floor)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (name) DO UPDATE
+            SET
+                damage_dealt = EXCLUDED.damage_dealt,
+                damage_taken = EXCLUDED.damage_taken,
+                damage_floor = EXCLUDED.damage_floor
+            "#,
+        )
+        .bind(&modifier.name)
+        .bind(modifier.damage_dealt)
+        .bind(modifier.damage_taken)
+        .bind(modifier.damage_floor)
+        .execute(&mut *txn)
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(http::StatusCode::OK)
+}
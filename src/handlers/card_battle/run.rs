@@ -0,0 +1,205 @@
+use axum::{extract, http, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+use crate::{error::AppError, handlers::matchmake::MatchQuery};
+
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct BattleRun {
+    pub id: uuid::Uuid,
+    section: String,
+    set: i32,
+    status: String, // "pending", "running", "completed", or "failed"
+    error: Option<String>,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    // Pacing of the live reveal, which the admin starts once the run is completed
+    pub turn_interval_ms: i32,
+    pub reveal_started_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// A run still marked as running after this long was cut off, e.g. by a server restart
+const STALE_RUN_MINUTES: i64 = 15;
+
+pub async fn get_battle_run(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Query(query): extract::Query<MatchQuery>,
+) -> Result<axum::Json<Option<BattleRun>>, AppError> {
+    let battle_run = sqlx::query_as::<_, BattleRun>(
+        "SELECT * FROM battle_runs WHERE section = ($1) AND set = ($2)",
+    )
+    .bind(query.section)
+    .bind(query.set)
+    .fetch_optional(&pool)
+    .await?;
+
+    Ok(axum::Json(battle_run))
+}
+
+pub async fn start_run(
+    pool: &PgPool,
+    section: &str,
+    set: i32,
+    rerun: bool,
+) -> Result<BattleRun, AppError> {
+    let mut txn = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO battle_runs (section, set) VALUES ($1, $2) ON CONFLICT (section, set) DO NOTHING",
+    )
+    .bind(section)
+    .bind(set)
+    .execute(&mut *txn)
+    .await?;
+
+    // Lock the run so the same set can't be simulated twice at the same time
+    let battle_run = sqlx::query_as::<_, BattleRun>(
+        "SELECT * FROM battle_runs WHERE section = ($1) AND set = ($2) FOR UPDATE",
+    )
+    .bind(section)
+    .bind(set)
+    .fetch_one(&mut *txn)
+    .await?;
+
+    let is_stale = !battle_run.started_at.is_some_and(|started_at| {
+        chrono::Utc::now() - started_at <= chrono::Duration::minutes(STALE_RUN_MINUTES)
+    });
+
+    match battle_run.status.as_str() {
+        "running" if !is_stale => {
+            return Err(AppError::new(
+                http::StatusCode::CONFLICT,
+                "The card battle of this set is already running.",
+            ));
+        }
+        // Only taken over on an explicit re-run
+        "running" if !rerun => {
+            return Err(AppError::new(
+                http::StatusCode::CONFLICT,
+                "The card battle of this set seems stuck. Re-run it to simulate it again.",
+            ));
+        }
+        "completed" if !rerun => {
+            return Err(AppError::new(
+                http::StatusCode::CONFLICT,
+                "The card battle of this set has already been completed. Re-run it to simulate it again.",
+            ));
+        }
+        _ => {}
+    }
+
+    let battle_run = sqlx::query_as::<_, BattleRun>(
+        r#"
+        UPDATE battle_runs
+        SET
+            status = 'running',
+            error = NULL,
+            started_at = NOW(),
+            finished_at = NULL,
+            reveal_started_at = NULL
+        WHERE id = ($1)
+        RETURNING *
+        "#,
+    )
+    .bind(battle_run.id)
+    .fetch_one(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+
+    Ok(battle_run)
+}
+
+pub async fn finish_run(
+    pool: &PgPool,
+    battle_run_id: &uuid::Uuid,
+    error: Option<&str>,
+) -> Result<BattleRun, AppError> {
+    let battle_run = sqlx::query_as::<_, BattleRun>(
+        r#"
+        UPDATE battle_runs
+        SET
+            status = CASE WHEN ($2)::TEXT IS NULL THEN 'completed' ELSE 'failed' END,
+            error = ($2),
+            finished_at = NOW()
+        WHERE id = ($1)
+        RETURNING *
+        "#,
+    )
+    .bind(battle_run_id)
+    .bind(error)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(battle_run)
+}
+
+// Takes back the scores awarded by the previous run and clears its results, called in the
+// transaction of the new run so a failed re-run keeps the previous results
+pub(super) async fn reverse_awards(
+    txn: &mut PgConnection,
+    section: &str,
+    set: i32,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        WITH Awarded AS (
+            SELECT user1_id AS user_id, user1_battle_score AS score
+            FROM match_sets
+            WHERE section = ($1) AND set = ($2) AND user1_battle_score IS NOT NULL
+            UNION ALL
+            SELECT user2_id AS user_id, user2_battle_score AS score
+            FROM match_sets
+            WHERE section = ($1) AND set = ($2) AND user2_battle_score IS NOT NULL
+        ), TotalAwarded AS (
+            SELECT user_id, SUM(score) AS score
+            FROM Awarded
+            GROUP BY user_id
+        )
+        UPDATE users u
+        SET score = u.score - ta.score
+        FROM TotalAwarded ta
+        WHERE u.id = ta.user_id
+        "#,
+    )
+    .bind(section)
+    .bind(set)
+    .execute(&mut *txn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM card_battle_history
+        WHERE match_set_id IN (SELECT id FROM match_sets WHERE section = ($1) AND set = ($2))
+        "#,
+    )
+    .bind(section)
+    .bind(set)
+    .execute(&mut *txn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE match_sets
+        SET
+            user1_total_damage = NULL,
+            user2_total_damage = NULL,
+            user1_battle_verdict = NULL,
+            user2_battle_verdict = NULL,
+            user1_battle_score = NULL,
+            user2_battle_score = NULL,
+            knockout_turn = NULL,
+            user1_remaining_hp = NULL,
+            user2_remaining_hp = NULL,
+            battle_snapshot = NULL
+        WHERE section = ($1) AND set = ($2)
+        "#,
+    )
+    .bind(section)
+    .bind(set)
+    .execute(&mut *txn)
+    .await?;
+
+    Ok(())
+}
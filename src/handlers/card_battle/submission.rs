@@ -0,0 +1,200 @@
+use axum::{extract, http, response::Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+
+use crate::{error::AppError, handlers::matchmake::MatchQuery};
+
+// Latest match of a user, the one their deck is submitted for
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeckMatch {
+    #[serde(rename = "match_set_id")]
+    pub id: uuid::Uuid,
+    pub deck_size: i16,
+    card_deadline: chrono::DateTime<chrono::Utc>,
+    extended_deadline: Option<chrono::DateTime<chrono::Utc>>,
+    battle_run_status: Option<String>,
+}
+
+impl DeckMatch {
+    pub async fn fetch(pool: &PgPool, user_id: &uuid::Uuid) -> Result<Self, AppError> {
+        let deck_match = sqlx::query_as::<_, DeckMatch>(
+            r#"
+            SELECT
+                ms.id,
+                ms.deck_size,
+                ms.card_deadline,
+                ext.deadline AS extended_deadline,
+                (
+                    SELECT br.status FROM battle_runs br
+                    WHERE br.section = ms.section AND br.set = ms.set
+                ) AS battle_run_status
+            FROM match_sets ms
+            LEFT JOIN battle_deck_extensions ext ON ext.match_set_id = ms.id AND ext.user_id = ($1)
+            WHERE ms.og_user1_id = ($1) OR ms.og_user2_id = ($1)
+            ORDER BY ms.created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::new(
+            http::StatusCode::NOT_FOUND,
+            "No match found for the user.",
+        ))?;
+
+        Ok(deck_match)
+    }
+
+    // Late decks are only accepted if the admin extended the deadline for the user, and never
+    // once the battle of the set has run since replays and re-runs would use the new cards
+    pub fn check_deadline(&self) -> Result<(), AppError> {
+        if matches!(
+            self.battle_run_status.as_deref(),
+            Some("running") | Some("completed")
+        ) {
+            return Err(AppError::with_details(
+                http::StatusCode::FORBIDDEN,
+                "The card battle of this set has already been run.",
+                serde_json::to_value(self)?,
+            ));
+        }
+
+        let deadline = self.extended_deadline.unwrap_or(self.card_deadline);
+
+        if chrono::Utc::now() <= deadline {
+            return Ok(());
+        }
+
+        Err(AppError::with_details(
+            http::StatusCode::FORBIDDEN,
+            "The card deadline has passed.",
+            serde_json::to_value(self)?,
+        ))
+    }
+}
+
+// Keeps the time of the first submission, late if it came after the original deadline,
+// later edits only move updated_at
+pub async fn record_submission(
+    txn: &mut PgConnection,
+    match_set_id: &uuid::Uuid,
+    user_id: &uuid::Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO battle_deck_submissions (match_set_id, user_id, is_late)
+        SELECT id, ($2), NOW() > card_deadline
+        FROM match_sets
+        WHERE id = ($1)
+        ON CONFLICT (match_set_id, user_id) DO UPDATE SET updated_at = NOW()
+        "#,
+    )
+    .bind(match_set_id)
+    .bind(user_id)
+    .execute(txn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateExtension {
+    match_set_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    deadline: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeckExtension {
+    match_set_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    deadline: chrono::DateTime<chrono::Utc>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// For admin
+pub async fn grant_extension(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Json(payload): extract::Json<CreateExtension>,
+) -> Result<axum::Json<DeckExtension>, AppError> {
+    let extension = sqlx::query_as::<_, DeckExtension>(
+        r#"
+        INSERT INTO battle_deck_extensions (match_set_id, user_id, deadline)
+        SELECT id, ($2), ($3)
+        FROM match_sets
+        WHERE id = ($1) AND (og_user1_id = ($2) OR og_user2_id = ($2))
+        ON CONFLICT (match_set_id, user_id) DO UPDATE
+        SET deadline = EXCLUDED.deadline, created_at = NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(payload.match_set_id)
+    .bind(payload.user_id)
+    .bind(payload.deadline)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(AppError::new(
+        http::StatusCode::NOT_FOUND,
+        "The user is not part of the match.",
+    ))?;
+
+    Ok(axum::Json(extension))
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeckSubmission {
+    match_set_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    first_name: String,
+    last_name: String,
+    card_deadline: chrono::DateTime<chrono::Utc>,
+    extended_deadline: Option<chrono::DateTime<chrono::Utc>>,
+    submitted_at: Option<chrono::DateTime<chrono::Utc>>, // None if the user has not submitted
+    updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    is_late: Option<bool>,
+}
+
+// For admin
+// Every user of the set, including the ones who have not submitted yet
+pub async fn get_submissions(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Query(query): extract::Query<MatchQuery>,
+) -> Result<axum::Json<Vec<DeckSubmission>>, AppError> {
+    let submissions = sqlx::query_as::<_, DeckSubmission>(
+        r#"
+        WITH MatchUsers AS (
+            SELECT id AS match_set_id, og_user1_id AS user_id, card_deadline
+            FROM match_sets
+            WHERE section = ($1) AND set = ($2)
+            UNION
+            SELECT id AS match_set_id, og_user2_id AS user_id, card_deadline
+            FROM match_sets
+            WHERE section = ($1) AND set = ($2)
+        )
+        SELECT
+            mu.match_set_id,
+            mu.user_id,
+            u.first_name,
+            u.last_name,
+            mu.card_deadline,
+            ext.deadline AS extended_deadline,
+            sub.submitted_at,
+            sub.updated_at,
+            sub.is_late
+        FROM MatchUsers mu
+        JOIN users u ON u.id = mu.user_id
+        LEFT JOIN battle_deck_extensions ext
+            ON ext.match_set_id = mu.match_set_id AND ext.user_id = mu.user_id
+        LEFT JOIN battle_deck_submissions sub
+            ON sub.match_set_id = mu.match_set_id AND sub.user_id = mu.user_id
+        ORDER BY u.last_name, u.first_name
+        "#,
+    )
+    .bind(query.section)
+    .bind(query.set)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(axum::Json(submissions))
+}
@@ -0,0 +1,217 @@
+use axum::{extract, http, response::Result};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+
+use crate::error::AppError;
+
+use super::CardBattle;
+
+#[derive(Debug, FromRow)]
+struct SummaryMatch {
+    user1_id: uuid::Uuid,
+    user2_id: uuid::Uuid,
+    user1_first_name: String,
+    user1_last_name: String,
+    user2_first_name: String,
+    user2_last_name: String,
+    user1_total_damage: Option<f32>,
+    user2_total_damage: Option<f32>,
+    user1_battle_verdict: Option<String>,
+    user2_battle_verdict: Option<String>,
+    knockout_turn: Option<i16>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BestTurn {
+    turn_number: i32,
+    card_name: Option<String>,
+    damage: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppliedEffect {
+    turn_number: i32,
+    card_name: Option<String>,
+    effect: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerSummary {
+    user_id: uuid::Uuid,
+    first_name: String,
+    last_name: String,
+    total_damage: Option<f32>,
+    verdict: Option<String>,
+    hits: usize,
+    // Strikes and feints that did not hit, including the ones that were cancelled or dodged
+    misses: usize,
+    // Blocks that cancelled the opponent's strike
+    cancels: usize,
+    best_turn: Option<BestTurn>,
+    effects: Vec<AppliedEffect>,
+}
+
+impl PlayerSummary {
+    fn new(
+        (user_id, first_name, last_name): (uuid::Uuid, String, String),
+        (total_damage, verdict): (Option<f32>, Option<String>),
+        history: &[CardBattle],
+    ) -> Self {
+        let turns: Vec<&CardBattle> = history.iter().filter(|h| h.user_id == user_id).collect();
+
+        let is_attack = |turn: &&&CardBattle| {
+            matches!(turn.card_skill.as_deref(), Some("strike") | Some("feint"))
+        };
+
+        // The earliest turn wins a tie
+        let best_turn = turns
+            .iter()
+            .filter(|turn| turn.damage > 0.0)
+            .fold(None::<&&CardBattle>, |best, turn| match best {
+                Some(best) if best.damage >= turn.damage => Some(best),
+                _ => Some(turn),
+            })
+            .map(|turn| BestTurn {
+                turn_number: turn.turn_number,
+                card_name: turn.card_name.clone(),
+                damage: turn.damage,
+            });
+
+        let effects = turns
+            .iter()
+            .filter_map(|turn| {
+                turn.card_effect.as_ref().map(|effect| AppliedEffect {
+                    turn_number: turn.turn_number,
+                    card_name: turn.card_name.clone(),
+                    effect: effect.clone(),
+                })
+            })
+            .collect();
+
+        PlayerSummary {
+            user_id,
+            first_name,
+            last_name,
+            total_damage,
+            verdict,
+            hits: turns.iter().filter(|turn| turn.is_hit).count(),
+            misses: turns
+                .iter()
+                .filter(is_attack)
+                .filter(|turn| !turn.is_hit)
+                .count(),
+            cancels: turns
+                .iter()
+                .filter(|turn| {
+                    turn.card_skill.as_deref() == Some("block")
+                        && turn.interaction.as_deref() == Some("cancel")
+                })
+                .count(),
+            best_turn,
+            effects,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BattleSummary {
+    match_set_id: uuid::Uuid,
+    // None on a draw
+    winner: Option<uuid::Uuid>,
+    is_draw: bool,
+    knockout_turn: Option<i16>,
+    user1: PlayerSummary,
+    user2: PlayerSummary,
+}
+
+// What the frontend shows after a match, worked out from the stored history
+pub async fn get_match_summary(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path(match_set_id): extract::Path<uuid::Uuid>,
+) -> Result<axum::Json<BattleSummary>, AppError> {
+    let summary_match = sqlx::query_as::<_, SummaryMatch>(
+        r#"
+        SELECT
+            ms.user1_id,
+            ms.user2_id,
+            u1.first_name AS user1_first_name,
+            u1.last_name AS user1_last_name,
+            u2.first_name AS user2_first_name,
+            u2.last_name AS user2_last_name,
+            ms.user1_total_damage,
+            ms.user2_total_damage,
+            ms.user1_battle_verdict,
+            ms.user2_battle_verdict,
+            ms.knockout_turn
+        FROM match_sets ms
+        JOIN users u1 ON u1.id = ms.user1_id
+        JOIN users u2 ON u2.id = ms.user2_id
+        WHERE ms.id = ($1)
+        "#,
+    )
+    .bind(match_set_id)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(AppError::new(
+        http::StatusCode::NOT_FOUND,
+        "Match not found.",
+    ))?;
+
+    // Verdicts are set for both players once the match is simulated, even on a forfeit
+    if summary_match.user1_battle_verdict.is_none() {
+        return Err(AppError::new(
+            http::StatusCode::NOT_FOUND,
+            "Match has not been simulated yet.",
+        ));
+    }
+
+    let history = sqlx::query_as::<_, CardBattle>(
+        "SELECT * FROM card_battle_history WHERE match_set_id = ($1) ORDER BY user_id, turn_number",
+    )
+    .bind(match_set_id)
+    .fetch_all(&pool)
+    .await?;
+
+    let SummaryMatch {
+        user1_id,
+        user2_id,
+        user1_battle_verdict,
+        user2_battle_verdict,
+        ..
+    } = summary_match;
+
+    let winner = match (
+        user1_battle_verdict.as_deref(),
+        user2_battle_verdict.as_deref(),
+    ) {
+        (Some("win"), _) => Some(user1_id),
+        (_, Some("win")) => Some(user2_id),
+        _ => None,
+    };
+    let is_draw = user1_battle_verdict.as_deref() == Some("draw");
+
+    Ok(axum::Json(BattleSummary {
+        match_set_id,
+        winner,
+        is_draw,
+        knockout_turn: summary_match.knockout_turn,
+        user1: PlayerSummary::new(
+            (
+                user1_id,
+                summary_match.user1_first_name,
+                summary_match.user1_last_name,
+            ),
+            (summary_match.user1_total_damage, user1_battle_verdict),
+            &history,
+        ),
+        user2: PlayerSummary::new(
+            (
+                user2_id,
+                summary_match.user2_first_name,
+                summary_match.user2_last_name,
+            ),
+            (summary_match.user2_total_damage, user2_battle_verdict),
+            &history,
+        ),
+    }))
+}
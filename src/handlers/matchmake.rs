@@ -31,17 +31,23 @@ pub struct Matchmake {
     user2_total_damage: Option<f32>,
     user1_arnis_verdict: Option<String>,
     user2_arnis_verdict: Option<String>,
+    user1_battle_verdict: Option<String>,
+    user2_battle_verdict: Option<String>,
     user1_score: Option<i32>,
     user2_score: Option<i32>,
     user1_des_count: i16,
     user2_des_count: i16,
     user1_ap_count: i16,
     user2_ap_count: i16,
+    deck_size: i16,
+    hp_pool: Option<f32>,
+    knockout_turn: Option<i16>,
+    user1_remaining_hp: Option<f32>,
+    user2_remaining_hp: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UserMatchQuery {
-    fields: Option<String>,
     limit: Option<i32>,
 }
 
@@ -259,6 +265,8 @@ pub struct Arnis {
     section: String,
     skill: String,
     footwork: String,
+    deck_size: Option<i16>, // Overrides the deck size of the section for this set
+    hp_pool: Option<f32>,   // Overrides the HP pool of the section, e.g. for tournament finals
 }
 
 pub async fn matchmake(
@@ -320,6 +328,10 @@ pub async fn matchmake(
                 -- Exclude the highest-ranked user if there's an odd number of users
                 CASE WHEN total_users % 2 <> 0 AND user_rank = total_users THEN TRUE ELSE FALSE END AS is_excluded
             FROM RankedUsers
+        ),
+        -- The excluded user plays against the bot instead of sitting out the set
+        Bot AS (
+            SELECT id FROM users WHERE role = 'bot' LIMIT 1
         )
         INSERT INTO match_sets (
             user1_id, 
@@ -330,7 +342,9 @@ pub async fn matchmake(
             arnis_skill, 
             arnis_footwork, 
             og_arnis_skill, 
-            set
+            set,
+            deck_size,
+            hp_pool
         )
         SELECT
             u1.id AS user1_id,
@@ -341,7 +355,9 @@ pub async fn matchmake(
             ($2) AS arnis_skill,
             ($3) AS arnis_footwork,
             ($2) AS og_arnis_skill,
-            (SELECT set FROM LatestMatch) + 1 AS set
+            (SELECT set FROM LatestMatch) + 1 AS set,
+            COALESCE(($4), (SELECT deck_size FROM sections WHERE id = ($1))) AS deck_size,
+            COALESCE(($5), (SELECT hp_pool FROM sections WHERE id = ($1))) AS hp_pool
         FROM
             AdjustedRankedUsers u1
         JOIN AdjustedRankedUsers u2 ON u1.user_rank = (u2.user_rank - 1) % u2.user_rank
@@ -360,9 +376,29 @@ pub async fn matchmake(
             ($2) AS arnis_skill,
             ($3) AS arnis_footwork,
             ($2) AS og_arnis_skill,
-            (SELECT set FROM LatestMatch) + 1 AS set
+            (SELECT set FROM LatestMatch) + 1 AS set,
+            COALESCE(($4), (SELECT deck_size FROM sections WHERE id = ($1))) AS deck_size,
+            COALESCE(($5), (SELECT hp_pool FROM sections WHERE id = ($1))) AS hp_pool
         FROM
             PersistedPairs
+
+        UNION
+
+        SELECT
+            u.id AS user1_id,
+            bot.id AS user2_id,
+            u.id AS og_user1_id,
+            bot.id AS og_user2_id,
+            ($1) AS section,
+            ($2) AS arnis_skill,
+            ($3) AS arnis_footwork,
+            ($2) AS og_arnis_skill,
+            (SELECT set FROM LatestMatch) + 1 AS set,
+            COALESCE(($4), (SELECT deck_size FROM sections WHERE id = ($1))) AS deck_size,
+            COALESCE(($5), (SELECT hp_pool FROM sections WHERE id = ($1))) AS hp_pool
+        FROM
+            AdjustedRankedUsers u, Bot bot
+        WHERE u.is_excluded = TRUE
         RETURNING *,
             (SELECT u1.first_name FROM users u1 WHERE u1.id = user1_id) AS user1_first_name,
             (SELECT u1.last_name FROM users u1 WHERE u1.id = user1_id) AS user1_last_name,
@@ -373,6 +409,8 @@ pub async fn matchmake(
     .bind(&payload.section)
     .bind(payload.skill)
     .bind(payload.footwork)
+    .bind(payload.deck_size)
+    .bind(payload.hp_pool)
     .fetch_all(&mut *txn)
     .await?;
 
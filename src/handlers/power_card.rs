@@ -1,7 +1,6 @@
 use crate::error::AppError;
 use axum::response::Result;
 use axum::{extract, http};
-use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, PgPool};
 
@@ -29,17 +28,6 @@ impl PowerCard {
 
         power_cards
     }
-
-    // Three (3) random cards per user, duplicates are allowed
-    fn get_random_cards(amount: usize) -> Vec<String> {
-        let power_cards = Self::get();
-
-        let mut rng = rand::thread_rng();
-        power_cards
-            .choose_multiple(&mut rng, amount)
-            .cloned()
-            .collect()
-    }
 }
 
 #[derive(Debug, Deserialize, Serialize, FromRow)]
@@ -68,7 +56,6 @@ pub async fn get_cards(
 pub struct InsertCard {
     name: Option<String>, // If the card is specified, only that card will be inserted
     user_id: uuid::Uuid,
-    amount: Option<usize>,
 }
 
 // #[derive(Debug, Deserialize)]
@@ -17,30 +17,6 @@ pub struct UpdateScore {
     match_set_id: uuid::Uuid,
 }
 
-pub async fn update_ranks(
-    extract::State(pool): extract::State<PgPool>,
-) -> Result<http::StatusCode, AppError> {
-    sqlx::query(
-        r#"
-        WITH OverallRank AS (
-            SELECT id, DENSE_RANK() OVER (ORDER BY score DESC) AS new_rank
-            FROM users
-        ), SectionRank AS (
-            SELECT id, DENSE_RANK() OVER (PARTITION BY section ORDER BY score DESC) AS new_rank
-            FROM users
-        )
-        UPDATE users u
-        SET rank_overall = ovr.new_rank, rank_section = sr.new_rank
-        FROM OverallRank ovr, SectionRank sr
-        WHERE u.id = ovr.id AND u.id = sr.id
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    Ok(http::StatusCode::OK)
-}
-
 // NOTE: This is horrible
 pub async fn update_score(
     extract::State(pool): extract::State<PgPool>,
@@ -139,9 +115,11 @@ pub async fn update_score(
         WITH OverallRank AS (
             SELECT id, DENSE_RANK() OVER (ORDER BY score DESC) AS new_rank
             FROM users
+            WHERE role <> 'bot'
         ), SectionRank AS (
             SELECT id, DENSE_RANK() OVER (PARTITION BY section ORDER BY score DESC) AS new_rank
             FROM users
+            WHERE role <> 'bot'
         )
         UPDATE users u
         SET rank_overall = ovr.new_rank, rank_section = sr.new_rank
@@ -1,14 +1,26 @@
 use axum::{extract, http, response::Result};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{types::Json, FromRow, PgPool};
 
-use crate::error::AppError;
+use crate::{
+    error::AppError,
+    handlers::card_battle::{
+        autofill::{BotDeckStrategy, MissingDeckPolicy},
+        catalog::Catalog,
+        model::DeckCard,
+    },
+};
 
 #[derive(Debug, Deserialize, Serialize, FromRow)]
 pub struct Section {
     id: String,
     name: String,
     user_limit: i32,
+    deck_size: i16,
+    missing_deck_policy: String,
+    default_deck: Json<Vec<DeckCard>>,
+    bot_deck_strategy: String,
+    hp_pool: Option<f32>,
 }
 
 #[derive(Debug, Deserialize, Serialize, FromRow)]
@@ -16,6 +28,11 @@ pub struct SectionWithUserCount {
     id: String,
     name: String,
     user_limit: i32,
+    deck_size: i16,
+    missing_deck_policy: String,
+    default_deck: Json<Vec<DeckCard>>,
+    bot_deck_strategy: String,
+    hp_pool: Option<f32>,
     user_count: i64,
 }
 
@@ -57,28 +74,124 @@ pub async fn get_sections_with_count(
 pub struct CreateSection {
     name: String,
     user_limit: i32,
+    deck_size: Option<i16>, // Number of cards in a battle deck, defaults to 6
+    missing_deck_policy: Option<MissingDeckPolicy>, // Defaults to forfeit
+    default_deck: Option<Vec<DeckCard>>,
+    bot_deck_strategy: Option<BotDeckStrategy>, // Defaults to random
+    hp_pool: Option<f32>,                       // HP knockout mode is off if None
+}
+
+// A typo in the default deck would only show up once the card battle is simulated
+async fn check_default_deck(pool: &PgPool, default_deck: &[DeckCard]) -> Result<(), AppError> {
+    let catalog = Catalog::fetch(pool).await?;
+    let unknown_cards: Vec<&DeckCard> = default_deck
+        .iter()
+        .filter(|card| catalog.get(&card.name, &card.skill).is_none())
+        .collect();
+
+    if unknown_cards.is_empty() {
+        return Ok(());
+    }
+
+    Err(AppError::with_details(
+        http::StatusCode::UNPROCESSABLE_ENTITY,
+        "Unknown battle cards in the default deck.",
+        serde_json::to_value(unknown_cards)?,
+    ))
 }
 
 pub async fn insert_section(
     extract::State(pool): extract::State<PgPool>,
     extract::Json(payload): extract::Json<CreateSection>,
 ) -> Result<axum::Json<Section>, AppError> {
+    let default_deck = payload.default_deck.unwrap_or_default();
+    check_default_deck(&pool, &default_deck).await?;
+
     let section = sqlx::query_as::<_, Section>(
         r#"
-        INSERT INTO sections (id, name, user_limit) 
-        VALUES ((LOWER(REPLACE(TRIM(BOTH ' ' FROM $1), ' ', '_'))), $1, $2) 
+        INSERT INTO sections (
+            id,
+            name,
+            user_limit,
+            deck_size,
+            missing_deck_policy,
+            default_deck,
+            bot_deck_strategy,
+            hp_pool
+        ) 
+        VALUES (
+            (LOWER(REPLACE(TRIM(BOTH ' ' FROM $1), ' ', '_'))),
+            $1,
+            $2,
+            COALESCE($3, 6),
+            COALESCE($4, 'forfeit'),
+            $5,
+            COALESCE($6, 'random'),
+            $7
+        ) 
         RETURNING *
         "#,
     )
     .bind(payload.name)
     .bind(payload.user_limit)
+    .bind(payload.deck_size)
+    .bind(payload.missing_deck_policy.map(|policy| policy.as_str()))
+    .bind(Json(default_deck))
+    .bind(payload.bot_deck_strategy.map(|strategy| strategy.as_str()))
+    .bind(payload.hp_pool)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(axum::Json(section))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSection {
+    user_limit: Option<i32>,
+    deck_size: Option<i16>, // Only applies to the sets matchmade after the change
+    missing_deck_policy: Option<MissingDeckPolicy>,
+    default_deck: Option<Vec<DeckCard>>,
+    bot_deck_strategy: Option<BotDeckStrategy>,
+    hp_pool: Option<f32>, // 0 turns the HP knockout mode off
+}
+
+pub async fn update_section(
+    extract::State(pool): extract::State<PgPool>,
+    extract::Path(section_id): extract::Path<String>,
+    extract::Json(payload): extract::Json<UpdateSection>,
+) -> Result<axum::Json<Section>, AppError> {
+    if let Some(default_deck) = &payload.default_deck {
+        check_default_deck(&pool, default_deck).await?;
+    }
+
+    let section = sqlx::query_as::<_, Section>(
+        r#"
+        UPDATE sections
+        SET
+            user_limit = COALESCE($2, user_limit),
+            deck_size = COALESCE($3, deck_size),
+            missing_deck_policy = COALESCE($4, missing_deck_policy),
+            default_deck = COALESCE($5, default_deck),
+            bot_deck_strategy = COALESCE($6, bot_deck_strategy),
+            hp_pool = NULLIF(COALESCE($7, hp_pool), 0)
+        WHERE id = ($1)
+        RETURNING *
+        "#,
+    )
+    .bind(section_id)
+    .bind(payload.user_limit)
+    .bind(payload.deck_size)
+    .bind(payload.missing_deck_policy.map(|policy| policy.as_str()))
+    .bind(payload.default_deck.map(Json))
+    .bind(payload.bot_deck_strategy.map(|strategy| strategy.as_str()))
+    .bind(payload.hp_pool)
     .fetch_one(&pool)
     .await?;
 
     Ok(axum::Json(section))
 }
 
-// TODO: Add update and delete functions
+// TODO: Add delete functions
 
 pub async fn delete_section(
     extract::State(pool): extract::State<PgPool>,
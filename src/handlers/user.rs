@@ -35,24 +35,6 @@ pub struct User {
     is_private: bool,
 }
 
-// Fetch user without their sensitive info
-// NOTE: Unfortunately, I'm not sure if there is a good way to dynamically fetch specific columns using
-// SQLx
-#[derive(Debug, Deserialize, Serialize, FromRow)]
-pub struct UserFetch {
-    id: uuid::Uuid,
-    section: String,
-    first_name: String,
-    last_name: String,
-    sex: i16,
-    rank_overall: i32,
-    rank_section: i32,
-    rank_title: Option<String>,
-    score: i32,
-    avatar_url: Option<String>,
-    banner_url: Option<String>,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct UsersQuery {
     section: Option<String>,
@@ -76,17 +58,18 @@ pub async fn get_users(
         query_builder.push("*");
     }
 
-    query_builder.push(" FROM users");
+    // The battle bot is not a student
+    query_builder.push(" FROM users WHERE role <> 'bot'");
 
     if let Some(section) = query.section {
         let sections: Vec<&str> = section.split(',').collect();
 
         if sections.len() == 1 {
-            query_builder.push(format_args!(" WHERE section = '{}'", section));
+            query_builder.push(format_args!(" AND section = '{}'", section));
         } else {
             let mut comma_sep = query_builder.separated(", ");
 
-            comma_sep.push_unseparated(" WHERE section IN (");
+            comma_sep.push_unseparated(" AND section IN (");
 
             for section in sections {
                 comma_sep.push(section);
@@ -216,9 +199,10 @@ pub struct UserCount {
 pub async fn get_users_count(
     extract::State(pool): extract::State<PgPool>,
 ) -> Result<axum::Json<UserCount>, AppError> {
-    let total = sqlx::query_as::<_, UserCount>("SELECT COUNT(*) AS total FROM users")
-        .fetch_one(&pool)
-        .await?;
+    let total =
+        sqlx::query_as::<_, UserCount>("SELECT COUNT(*) AS total FROM users WHERE role <> 'bot'")
+            .fetch_one(&pool)
+            .await?;
 
     Ok(axum::Json(total))
 }
@@ -250,10 +234,12 @@ pub async fn get_user(
 
         Ok(axum::Json(user_value))
     } else {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ($1)")
-            .bind(user_id)
-            .fetch_one(&pool)
-            .await?;
+        // Only the name of the bot is shown, as the opponent of a match
+        let user =
+            sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ($1) AND role <> 'bot'")
+                .bind(user_id)
+                .fetch_one(&pool)
+                .await?;
         let user_value = serde_json::to_value(user)?;
 
         Ok(axum::Json(user_value))
@@ -315,9 +301,11 @@ pub async fn update_user(
         WITH OverallRank AS (
             SELECT id, DENSE_RANK() OVER (ORDER BY score DESC) AS new_rank
             FROM users
+            WHERE role <> 'bot'
         ), SectionRank AS (
             SELECT id, DENSE_RANK() OVER (PARTITION BY section ORDER BY score DESC) AS new_rank
             FROM users
+            WHERE role <> 'bot'
         )
         UPDATE users u
         SET rank_overall = ovr.new_rank, rank_section = sr.new_rank
@@ -522,9 +510,11 @@ pub async fn delete_users(
         WITH OverallRank AS (
             SELECT id, DENSE_RANK() OVER (ORDER BY score DESC) AS new_rank
             FROM users
+            WHERE role <> 'bot'
         ), SectionRank AS (
             SELECT id, DENSE_RANK() OVER (PARTITION BY section ORDER BY score DESC) AS new_rank
             FROM users
+            WHERE role <> 'bot'
         )
         UPDATE users u
         SET rank_overall = ovr.new_rank, rank_section = sr.new_rank
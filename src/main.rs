@@ -5,7 +5,7 @@
 use anyhow::Context;
 use axum::{
     http,
-    routing::{get, patch, post},
+    routing::{delete, get, patch, post},
     Router,
 };
 use dotenv::dotenv;
@@ -85,6 +85,7 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
                 .delete(section::delete_section),
         )
         .route("/sections/count", get(section::get_sections_with_count))
+        .route("/sections/:section_id", patch(section::update_section))
         // Power Card
         .route(
             "/power_cards",
@@ -107,12 +108,89 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
         // Card Battle
         .route(
             "/card_battle",
-            get(card_battle::card_battle).post(card_battle::insert_cards),
+            post(card_battle::insert_cards).put(card_battle::replace_cards),
+        )
+        .route("/card_battle/deck", get(card_battle::get_deck))
+        .route(
+            "/card_battle/deck/:turn_number",
+            patch(card_battle::update_card),
+        )
+        .route(
+            "/card_battle/run",
+            get(card_battle::run::get_battle_run).post(card_battle::card_battle),
+        )
+        .route(
+            "/card_battle/run/reveal",
+            get(card_battle::reveal::reveal_battle_run).post(card_battle::reveal::start_reveal),
+        )
+        .route(
+            "/card_battle/catalog",
+            get(card_battle::catalog::get_catalog).post(card_battle::catalog::insert_catalog_card),
+        )
+        .route(
+            "/card_battle/catalog/:skill/:name",
+            patch(card_battle::catalog::update_catalog_card),
+        )
+        .route(
+            "/card_battle/rewards",
+            get(card_battle::reward::get_rewards).patch(card_battle::reward::update_rewards),
+        )
+        .route(
+            "/card_battle/modifiers/skill",
+            get(card_battle::rules::get_skill_modifiers)
+                .patch(card_battle::rules::update_skill_modifiers),
+        )
+        .route(
+            "/card_battle/modifiers/footwork",
+            get(card_battle::rules::get_footwork_modifiers)
+                .patch(card_battle::rules::update_footwork_modifiers),
+        )
+        .route(
+            "/card_battle/modifiers/power_card",
+            get(card_battle::rules::get_power_card_modifiers)
+                .patch(card_battle::rules::update_power_card_modifiers),
+        )
+        .route(
+            "/card_battle/combos",
+            get(card_battle::combo::get_combos).patch(card_battle::combo::update_combos),
+        )
+        .route(
+            "/card_battle/combos/:name",
+            delete(card_battle::combo::delete_combo),
+        )
+        .route(
+            "/card_battle/submissions",
+            get(card_battle::submission::get_submissions),
+        )
+        .route(
+            "/card_battle/extensions",
+            post(card_battle::submission::grant_extension),
+        )
+        .route("/card_battle/simulate", post(card_battle::simulate_battle))
+        .route(
+            "/card_battle/analysis",
+            post(card_battle::analysis::analyze_matchup),
+        )
+        .route(
+            "/card_battle/balance",
+            get(card_battle::balance::get_card_balance),
         )
         .route(
             "/card_battle/:match_set_id",
             get(card_battle::get_match_results),
         )
+        .route(
+            "/card_battle/:match_set_id/summary",
+            get(card_battle::summary::get_match_summary),
+        )
+        .route(
+            "/card_battle/:match_set_id/log",
+            get(card_battle::get_match_log),
+        )
+        .route(
+            "/card_battle/:match_set_id/replay",
+            get(card_battle::replay_match),
+        )
         .layer(CorsLayer::permissive())
         .with_state(pool);
 